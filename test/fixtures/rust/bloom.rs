@@ -0,0 +1,95 @@
+//! Counting bloom filter for fast "definitely absent" checks.
+//!
+//! Each item bumps `HASHES` counters instead of setting bits, so removing an
+//! item that was inserted keeps the filter exact about what may be present:
+//! lookups never give false negatives, only occasional false positives.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Counters per expected item; with `HASHES` = 7 this gives roughly 1% false positives.
+const COUNTERS_PER_ITEM: usize = 10;
+const HASHES: u64 = 7;
+
+/// Counting bloom filter over string keys, safe to query without a lock.
+#[derive(Debug)]
+pub struct CountingBloom {
+    counters: Box<[AtomicU32]>,
+}
+
+impl CountingBloom {
+    /// Create a filter sized for about `expected_items` keys.
+    pub fn new(expected_items: usize) -> Self {
+        let size = expected_items.max(1) * COUNTERS_PER_ITEM;
+        CountingBloom {
+            counters: (0..size).map(|_| AtomicU32::new(0)).collect(),
+        }
+    }
+
+    /// Record one occurrence of `item`.
+    pub fn insert(&self, item: &str) {
+        for i in self.indexes(item) {
+            self.counters[i].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Forget one occurrence of `item`; only call this for items that were inserted.
+    pub fn remove(&self, item: &str) {
+        for i in self.indexes(item) {
+            let counter = &self.counters[i];
+            let _ = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |c| c.checked_sub(1));
+        }
+    }
+
+    /// Check if `item` may be present; `false` means it definitely is not.
+    pub fn probably_contains(&self, item: &str) -> bool {
+        self.indexes(item).all(|i| self.counters[i].load(Ordering::Relaxed) > 0)
+    }
+
+    /// Forget every item.
+    pub fn clear(&self) {
+        for counter in self.counters.iter() {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Counter positions for `item`, via double hashing.
+    fn indexes(&self, item: &str) -> impl Iterator<Item = usize> {
+        let mut hasher = DefaultHasher::new();
+        item.hash(&mut hasher);
+        let h1 = hasher.finish();
+        h1.hash(&mut hasher);
+        let h2 = hasher.finish() | 1;
+        let size = self.counters.len() as u64;
+        (0..HASHES).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % size) as usize)
+    }
+}
+
+impl Clone for CountingBloom {
+    fn clone(&self) -> Self {
+        CountingBloom {
+            counters: self
+                .counters
+                .iter()
+                .map(|c| AtomicU32::new(c.load(Ordering::Relaxed)))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counting_bloom_remove() {
+        let bloom = CountingBloom::new(16);
+        bloom.insert("a");
+        bloom.insert("a");
+        bloom.remove("a");
+        assert!(bloom.probably_contains("a"));
+        bloom.remove("a");
+        assert!(!bloom.probably_contains("a"));
+    }
+}
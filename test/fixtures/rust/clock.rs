@@ -0,0 +1,85 @@
+//! Time sources, injectable so expiry logic can be tested without sleeping.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Source of the current time.
+pub trait Clock: Send + Sync {
+    /// Get the current monotonic time.
+    fn now(&self) -> Instant;
+
+    /// Get the current wall-clock time.
+    fn system_now(&self) -> SystemTime;
+}
+
+/// Clock reading the real system time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Clock that only moves when `advance` is called.
+#[derive(Debug)]
+#[allow(dead_code)]
+pub struct MockClock {
+    start: Instant,
+    system_start: SystemTime,
+    offset: Mutex<Duration>,
+}
+
+#[allow(dead_code)]
+impl MockClock {
+    /// Create a clock frozen at the current time.
+    pub fn new() -> Self {
+        MockClock {
+            start: Instant::now(),
+            system_start: SystemTime::now(),
+            offset: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Move the clock forward.
+    pub fn advance(&self, by: Duration) {
+        *self.offset.lock().unwrap() += by;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + *self.offset.lock().unwrap()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        self.system_start + *self.offset.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock_advances_manually() {
+        let clock = MockClock::new();
+        let (start, system_start) = (clock.now(), clock.system_now());
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(clock.now() - start, Duration::from_secs(90));
+        assert_eq!(clock.system_now().duration_since(system_start).unwrap(), Duration::from_secs(90));
+    }
+}
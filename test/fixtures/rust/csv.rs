@@ -0,0 +1,75 @@
+//! Minimal RFC 4180 CSV reading and writing.
+
+/// Quote a field if it contains a comma, quote, or line break.
+pub fn escape_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Join fields into one CSV line, without a line terminator.
+pub fn write_record(fields: &[&str]) -> String {
+    fields.iter().map(|f| escape_field(f)).collect::<Vec<_>>().join(",")
+}
+
+/// Parse CSV text into records; quoted fields may contain commas, `""` and line breaks.
+pub fn parse_records(input: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err("Unterminated quoted field".to_string());
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_quoted_fields() {
+        let line = write_record(&["1", "Ship it, then \"celebrate\"", "multi\nline"]);
+        assert_eq!(line, "1,\"Ship it, then \"\"celebrate\"\"\",\"multi\nline\"");
+        let records = parse_records(&format!("{}\r\nplain,row,\n", line)).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                vec!["1", "Ship it, then \"celebrate\"", "multi\nline"],
+                vec!["plain", "row", ""],
+            ]
+        );
+        assert!(parse_records("\"open").is_err());
+    }
+}
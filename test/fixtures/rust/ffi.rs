@@ -0,0 +1,102 @@
+//! C-compatible bindings for core `TaskManager` operations.
+//!
+//! Managers are handed out as opaque pointers from [`task_manager_new`] and
+//! must be released with [`task_manager_free`]. Every function tolerates null
+//! pointers and reports failure through its return value instead of panicking.
+
+use crate::{Task, TaskManager};
+use std::ffi::{c_char, c_int, CStr};
+
+/// Returned when an argument is null, not UTF-8, or the operation failed.
+pub const FFI_ERROR: c_int = -1;
+
+/// Create a task manager; release it with [`task_manager_free`].
+#[no_mangle]
+pub extern "C" fn task_manager_new() -> *mut TaskManager {
+    Box::into_raw(Box::new(TaskManager::new()))
+}
+
+/// Free a manager created by [`task_manager_new`]; null is ignored.
+///
+/// # Safety
+///
+/// `manager` must be null or a pointer from [`task_manager_new`] that has not
+/// been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn task_manager_free(manager: *mut TaskManager) {
+    if !manager.is_null() {
+        drop(Box::from_raw(manager));
+    }
+}
+
+/// Add a task, returning 0 on success or [`FFI_ERROR`].
+///
+/// # Safety
+///
+/// `manager` must be null or a live pointer from [`task_manager_new`]; `id` and
+/// `name` must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn task_manager_add_task(
+    manager: *const TaskManager,
+    id: *const c_char,
+    name: *const c_char,
+) -> c_int {
+    let (Some(manager), Some(id), Some(name)) = (manager.as_ref(), to_str(id), to_str(name)) else {
+        return FFI_ERROR;
+    };
+    match manager.add_task(Task::new(id.to_string(), name.to_string())) {
+        Ok(()) => 0,
+        Err(_) => FFI_ERROR,
+    }
+}
+
+/// Get a task's status code (see `Status::as_u8`), or [`FFI_ERROR`] if it is missing.
+///
+/// # Safety
+///
+/// `manager` must be null or a live pointer from [`task_manager_new`]; `id`
+/// must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn task_manager_task_status(
+    manager: *const TaskManager,
+    id: *const c_char,
+) -> c_int {
+    let (Some(manager), Some(id)) = (manager.as_ref(), to_str(id)) else {
+        return FFI_ERROR;
+    };
+    manager
+        .get_task(id)
+        .map_or(FFI_ERROR, |task| c_int::from(task.status.as_u8()))
+}
+
+/// Borrow a C string as UTF-8; null or invalid input gives `None`.
+unsafe fn to_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn test_ffi_round_trip() {
+        let manager = task_manager_new();
+        unsafe {
+            assert_eq!(task_manager_add_task(manager, c"1".as_ptr(), c"Write docs".as_ptr()), 0);
+            assert_eq!(task_manager_add_task(manager, c"2".as_ptr(), c"".as_ptr()), FFI_ERROR);
+            assert_eq!(task_manager_add_task(manager, ptr::null(), c"x".as_ptr()), FFI_ERROR);
+            assert_eq!(task_manager_add_task(ptr::null(), c"3".as_ptr(), c"x".as_ptr()), FFI_ERROR);
+
+            assert_eq!(task_manager_task_status(manager, c"1".as_ptr()), 0);
+            assert_eq!(task_manager_task_status(manager, c"missing".as_ptr()), FFI_ERROR);
+            assert_eq!(task_manager_task_status(manager, ptr::null()), FFI_ERROR);
+
+            task_manager_free(manager);
+            task_manager_free(ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Serialization formats for task export and import.
+//!
+//! Each format is gated behind its own feature: `json`, `yaml` and `msgpack`.
+//! YAML and MessagePack go through serde using [`TaskRecord`]; JSON reuses the
+//! manager's own `export_json` / `import_json`.
+
+/// Wire format for `TaskManager::export` and `TaskManager::import`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    #[cfg(feature = "msgpack")]
+    MsgPack,
+}
+
+#[cfg(any(feature = "yaml", feature = "msgpack"))]
+pub use record::TaskRecord;
+
+#[cfg(any(feature = "yaml", feature = "msgpack"))]
+mod record {
+    use crate::{validate_task, Task};
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    /// Serde-friendly copy of a task's exported fields, with metadata sorted by key.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct TaskRecord {
+        pub id: String,
+        pub name: String,
+        pub status: String,
+        pub priority: i32,
+        #[serde(default)]
+        pub metadata: BTreeMap<String, String>,
+    }
+
+    impl From<&Task> for TaskRecord {
+        fn from(task: &Task) -> Self {
+            TaskRecord {
+                id: task.id.clone(),
+                name: task.name.clone(),
+                status: format!("{:?}", task.status),
+                priority: task.priority,
+                metadata: task.metadata.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            }
+        }
+    }
+
+    impl TaskRecord {
+        /// Turn the record back into a validated task.
+        pub fn into_task(self) -> Result<Task, String> {
+            let mut task = Task::new(self.id, self.name)
+                .with_status(self.status.parse()?)
+                .with_priority(self.priority);
+            task.metadata = self.metadata.into_iter().collect();
+            validate_task(&task)?;
+            Ok(task)
+        }
+    }
+}
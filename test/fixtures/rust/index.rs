@@ -0,0 +1,90 @@
+//! Inverted index over task names and metadata values.
+//!
+//! Text is split into lowercase alphanumeric terms. Terms live in a sorted map,
+//! so a prefix lookup is a range scan rather than a pass over every task.
+
+use crate::Task;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Term-to-task-ID index supporting prefix search.
+#[derive(Debug, Clone, Default)]
+pub struct TextIndex {
+    terms: BTreeMap<String, HashSet<String>>,
+    docs: HashMap<String, Vec<String>>,
+}
+
+impl TextIndex {
+    /// Create an empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index a task, replacing any terms previously indexed for its ID.
+    pub fn insert(&mut self, task: &Task) {
+        self.remove(&task.id);
+        let mut terms: Vec<String> = tokenize(&task.name)
+            .chain(task.metadata.values().flat_map(|v| tokenize(v)))
+            .collect();
+        terms.sort();
+        terms.dedup();
+        for term in &terms {
+            self.terms.entry(term.clone()).or_default().insert(task.id.clone());
+        }
+        self.docs.insert(task.id.clone(), terms);
+    }
+
+    /// Drop a task's terms from the index.
+    pub fn remove(&mut self, id: &str) {
+        for term in self.docs.remove(id).into_iter().flatten() {
+            if let Some(ids) = self.terms.get_mut(&term) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    self.terms.remove(&term);
+                }
+            }
+        }
+    }
+
+    /// Get IDs of tasks with a term starting with `prefix`, ignoring case.
+    pub fn search(&self, prefix: &str) -> HashSet<&str> {
+        let prefix = prefix.to_lowercase();
+        self.terms
+            .range(prefix.clone()..)
+            .take_while(|(term, _)| term.starts_with(&prefix))
+            .flat_map(|(_, ids)| ids.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// Remove every entry.
+    pub fn clear(&mut self) {
+        self.terms.clear();
+        self.docs.clear();
+    }
+}
+
+/// Split text into lowercase alphanumeric terms.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_prefix_search() {
+        let mut index = TextIndex::new();
+        let tagged = Task::new("1".to_string(), "Deploy API".to_string()).with_metadata("env", "prod-eu");
+        index.insert(&tagged);
+        index.insert(&Task::new("2".to_string(), "Depot audit".to_string()));
+        assert_eq!(index.search("DEP").len(), 2);
+        assert_eq!(index.search("eu").into_iter().collect::<Vec<_>>(), vec!["1"]);
+
+        index.insert(&Task::new("1".to_string(), "Renamed".to_string()));
+        assert_eq!(index.search("deploy").len(), 0);
+        index.remove("2");
+        assert!(index.search("dep").is_empty());
+    }
+}
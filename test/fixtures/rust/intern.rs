@@ -0,0 +1,54 @@
+//! String interning for metadata keys shared across many tasks.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Pool of shared strings; interning equal content returns the same allocation.
+#[derive(Debug, Default, Clone)]
+pub struct Interner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    /// Create an empty pool.
+    #[allow(dead_code)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the pooled copy of `s`, adding it on first use.
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(existing) = self.strings.get(s) {
+            return existing.clone();
+        }
+        let shared: Arc<str> = Arc::from(s);
+        self.strings.insert(shared.clone());
+        shared
+    }
+
+    /// Get the number of distinct pooled strings.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Check if the pool is empty.
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_shares_allocation() {
+        let mut interner = Interner::new();
+        let a = interner.intern("owner");
+        let b = interner.intern(&String::from("owner"));
+        assert!(Arc::ptr_eq(&a, &b));
+        interner.intern("team");
+        assert_eq!(interner.len(), 2);
+    }
+}
@@ -0,0 +1,10 @@
+//! Library target exposing the `alloc`-only utilities.
+//!
+//! Without the default `std` feature this builds as `no_std`, so
+//! `cargo build --lib --no-default-features` checks that the core stays std-free.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod utils_core;
@@ -0,0 +1,16 @@
+//! Optional logging through the `log` crate.
+//!
+//! `log_at!(level, ...)` forwards to the matching `log` macro when the
+//! `logging` feature is enabled and only type-checks its arguments otherwise,
+//! so call sites don't need their own `cfg` attributes.
+
+/// Log a message at the given `log` level (`error`, `warn`, `info`, `debug`, `trace`).
+macro_rules! log_at {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "logging")]
+        log::$level!($($arg)+);
+        // Still type-check the arguments so they count as used without logging.
+        #[cfg(not(feature = "logging"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
@@ -1,21 +1,124 @@
 //! Main Rust test fixtures.
 //! Tests structs, traits, enums, and async functions.
 
+#[macro_use]
+mod logging;
+mod bloom;
+mod checksum;
+mod clock;
+mod csv;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(any(feature = "json", feature = "yaml", feature = "msgpack"))]
+mod format;
+mod index;
+mod intern;
+mod query;
+mod retry;
+mod rng;
 mod service;
+mod sink;
+mod store;
+mod timing;
 mod utils;
+mod utils_core;
+mod wal;
+#[cfg(feature = "wasm")]
+mod wasm;
 
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+extern crate alloc;
+
+use bloom::CountingBloom;
+use clock::{Clock, SystemClock};
+#[cfg(any(feature = "json", feature = "yaml", feature = "msgpack"))]
+use format::Format;
+use index::TextIndex;
+use rng::Rng;
+use sink::CoalescingSink;
+use std::borrow::{Borrow, Cow};
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use store::{TaskStore, TimedGuard};
+use wal::{Wal, WalRecord};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, SystemTime};
 
 /// Status enum representing task states.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Status {
     Pending,
+    Claimed,
     Active,
     Completed,
     Failed,
 }
 
+impl Status {
+    /// Position in the task lifecycle; terminal states share the highest rank.
+    fn lifecycle_rank(&self) -> u8 {
+        match self {
+            Status::Pending => 0,
+            Status::Claimed => 1,
+            Status::Active => 2,
+            Status::Completed | Status::Failed => 3,
+        }
+    }
+
+    /// Stable integer code for compact serialization and FFI.
+    ///
+    /// Pending = 0, Claimed = 1, Active = 2, Completed = 3, Failed = 4. Codes are
+    /// never reused; new variants get the next free value.
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            Status::Pending => 0,
+            Status::Claimed => 1,
+            Status::Active => 2,
+            Status::Completed => 3,
+            Status::Failed => 4,
+        }
+    }
+
+    /// Decode a code produced by [`Status::as_u8`].
+    pub fn from_u8(code: u8) -> Option<Status> {
+        match code {
+            0 => Some(Status::Pending),
+            1 => Some(Status::Claimed),
+            2 => Some(Status::Active),
+            3 => Some(Status::Completed),
+            4 => Some(Status::Failed),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Status {
+    type Err = String;
+
+    /// Parse the `Debug` name of a status, as written by `format_task`.
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "Pending" => Ok(Status::Pending),
+            "Claimed" => Ok(Status::Claimed),
+            "Active" => Ok(Status::Active),
+            "Completed" => Ok(Status::Completed),
+            "Failed" => Ok(Status::Failed),
+            _ => Err(format!("Unknown status '{}'", s)),
+        }
+    }
+}
+
+/// Lease held by a worker that has claimed a task.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Claim {
+    pub worker_id: String,
+    pub expires_at: SystemTime,
+}
+
 /// Task struct representing a task entity.
 #[derive(Debug, Clone)]
 pub struct Task {
@@ -24,6 +127,13 @@ pub struct Task {
     pub status: Status,
     pub priority: i32,
     pub metadata: HashMap<String, String>,
+    pub created_at: SystemTime,
+    pub version: u64,
+    pub expires_at: Option<SystemTime>,
+    pub claim: Option<Claim>,
+    pub deadline: Option<SystemTime>,
+    /// IDs of tasks this task depends on.
+    pub depends_on: Vec<String>,
 }
 
 impl Task {
@@ -35,15 +145,138 @@ impl Task {
             status: Status::Pending,
             priority: 1,
             metadata: HashMap::new(),
+            created_at: SystemTime::now(),
+            version: 0,
+            expires_at: None,
+            claim: None,
+            deadline: None,
+            depends_on: Vec::new(),
         }
     }
 
+    /// Turn the task back into a builder pre-populated with all its fields.
+    pub fn to_builder(self) -> TaskBuilder {
+        TaskBuilder { task: self }
+    }
+
     /// Set the task priority.
     pub fn with_priority(mut self, priority: i32) -> Self {
         self.priority = priority;
         self
     }
 
+    /// Set a metadata entry.
+    pub fn with_metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Look up a metadata value ignoring key case.
+    ///
+    /// An exact match wins; otherwise the first matching key in sorted order is used.
+    pub fn metadata_get_ci(&self, key: &str) -> Option<&String> {
+        if let Some(value) = self.metadata.get(key) {
+            return Some(value);
+        }
+        let wanted = key.to_lowercase();
+        self.metadata
+            .iter()
+            .filter(|(k, _)| k.to_lowercase() == wanted)
+            .min_by(|a, b| a.0.cmp(b.0))
+            .map(|(_, v)| v)
+    }
+
+    /// Lowercase all metadata keys.
+    ///
+    /// Keys are applied in sorted order, so on collision the last key in that
+    /// order wins (an already-lowercase key beats its capitalised variants).
+    pub fn normalize_metadata_keys(&mut self) {
+        let mut entries: Vec<(String, String)> = self.metadata.drain().collect();
+        entries.sort();
+        for (key, value) in entries {
+            self.metadata.insert(key.to_lowercase(), value);
+        }
+    }
+
+    /// List what changed going from this task to `other`.
+    pub fn diff(&self, other: &Task) -> TaskDiff {
+        let mut diff = TaskDiff::default();
+        if self.name != other.name {
+            diff.fields.push("name");
+        }
+        if self.status != other.status {
+            diff.fields.push("status");
+        }
+        if self.priority != other.priority {
+            diff.fields.push("priority");
+        }
+        for (key, value) in &other.metadata {
+            match self.metadata.get(key) {
+                None => diff.metadata_added.push(key.clone()),
+                Some(old) if old != value => diff.metadata_changed.push(key.clone()),
+                Some(_) => {}
+            }
+        }
+        for key in self.metadata.keys() {
+            if !other.metadata.contains_key(key) {
+                diff.metadata_removed.push(key.clone());
+            }
+        }
+        diff.metadata_added.sort();
+        diff.metadata_removed.sort();
+        diff.metadata_changed.sort();
+        diff
+    }
+
+    /// Reconcile two versions of the same task.
+    ///
+    /// Takes the higher priority and the further-along status (`other` wins
+    /// ties), unions metadata with `other` winning conflicts, and keeps the
+    /// earliest `created_at` and highest `version`. Remaining fields come from `other`.
+    pub fn merge(&self, other: &Task) -> Result<Task, String> {
+        if self.id != other.id {
+            return Err(format!("Cannot merge task {} with task {}", self.id, other.id));
+        }
+        let mut merged = other.clone();
+        merged.priority = self.priority.max(other.priority);
+        if self.status.lifecycle_rank() > other.status.lifecycle_rank() {
+            merged.status = self.status.clone();
+        }
+        merged.metadata = self.metadata.clone();
+        merged.metadata.extend(other.metadata.clone());
+        merged.created_at = self.created_at.min(other.created_at);
+        merged.version = self.version.max(other.version);
+        Ok(merged)
+    }
+
+    /// Set the time after which a pending task is considered stale.
+    pub fn with_expiry(mut self, expires_at: SystemTime) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Check if the task is still pending past its expiry time.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.status == Status::Pending && self.expires_at.is_some_and(|at| at <= now)
+    }
+
+    /// Add a dependency on the task with `id`.
+    pub fn with_dependency(mut self, id: &str) -> Self {
+        self.depends_on.push(id.to_string());
+        self
+    }
+
+    /// Set the expected completion deadline.
+    pub fn with_deadline(mut self, deadline: SystemTime) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Check if the task is still incomplete past its deadline.
+    pub fn is_breached(&self, now: SystemTime) -> bool {
+        !self.is_complete() && self.deadline.is_some_and(|at| at <= now)
+    }
+
     /// Set the task status.
     pub fn with_status(mut self, status: Status) -> Self {
         self.status = status;
@@ -54,200 +287,3220 @@ impl Task {
     pub fn is_complete(&self) -> bool {
         self.status == Status::Completed
     }
-}
-
-/// Trait for entities with an ID.
-pub trait Entity {
-    fn get_id(&self) -> &str;
-}
 
-impl Entity for Task {
-    fn get_id(&self) -> &str {
-        &self.id
+    /// Apply a field update and bump the version.
+    fn apply_update(&mut self, name: Option<String>, status: Option<Status>) {
+        if let Some(n) = name {
+            self.name = n;
+        }
+        if let Some(s) = status {
+            self.status = s;
+        }
+        self.version += 1;
     }
 }
 
-/// Task manager that manages a collection of tasks.
-pub struct TaskManager {
-    tasks: Arc<Mutex<Vec<Task>>>,
+/// Builder for creating or modifying a task.
+#[derive(Debug, Clone)]
+pub struct TaskBuilder {
+    task: Task,
 }
 
-impl TaskManager {
-    /// Create a new task manager.
-    pub fn new() -> Self {
-        TaskManager {
-            tasks: Arc::new(Mutex::new(Vec::new())),
+impl TaskBuilder {
+    /// Start a builder for a new pending task.
+    pub fn new(id: &str, name: &str) -> Self {
+        TaskBuilder {
+            task: Task::new(id.to_string(), name.to_string()),
         }
     }
 
-    /// Add a task to the manager.
-    pub fn add_task(&self, task: Task) -> Result<(), String> {
-        validate_task(&task)?;
-        let mut tasks = self.tasks.lock().unwrap();
-        tasks.push(task);
-        Ok(())
+    /// Set the task name.
+    pub fn name(mut self, name: &str) -> Self {
+        self.task.name = name.to_string();
+        self
     }
 
-    /// Get a task by ID.
-    pub fn get_task(&self, id: &str) -> Option<Task> {
-        let tasks = self.tasks.lock().unwrap();
-        tasks.iter().find(|t| t.id == id).cloned()
+    /// Set the task status.
+    pub fn status(mut self, status: Status) -> Self {
+        self.task.status = status;
+        self
     }
 
-    /// Get all tasks, optionally filtered.
-    pub fn get_tasks<F>(&self, filter: Option<F>) -> Vec<Task>
-    where
-        F: Fn(&Task) -> bool,
-    {
-        let tasks = self.tasks.lock().unwrap();
-        match filter {
-            Some(f) => tasks.iter().filter(|t| f(t)).cloned().collect(),
-            None => tasks.clone(),
-        }
+    /// Set the task priority.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.task.priority = priority;
+        self
     }
 
-    /// Update a task by ID.
-    pub fn update_task(&self, id: &str, name: Option<String>, status: Option<Status>) -> Option<Task> {
-        let mut tasks = self.tasks.lock().unwrap();
-        for task in tasks.iter_mut() {
-            if task.id == id {
-                if let Some(n) = name {
-                    task.name = n;
-                }
-                if let Some(s) = status {
-                    task.status = s;
-                }
-                return Some(task.clone());
-            }
+    /// Set a metadata entry.
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.task.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Set the expiry time.
+    pub fn expires_at(mut self, expires_at: Option<SystemTime>) -> Self {
+        self.task.expires_at = expires_at;
+        self
+    }
+
+    /// Set the expected completion deadline.
+    pub fn deadline(mut self, deadline: Option<SystemTime>) -> Self {
+        self.task.deadline = deadline;
+        self
+    }
+
+    /// Finish building the task.
+    pub fn build(self) -> Task {
+        self.task
+    }
+}
+
+/// Defaults stamped onto every task created from it.
+#[derive(Debug, Clone)]
+pub struct TaskTemplate {
+    pub priority: i32,
+    pub status: Status,
+    pub metadata: HashMap<String, String>,
+}
+
+impl TaskTemplate {
+    /// Create a template matching `Task::new` defaults.
+    pub fn new() -> Self {
+        TaskTemplate {
+            priority: 1,
+            status: Status::Pending,
+            metadata: HashMap::new(),
         }
-        None
     }
 
-    /// Delete a task by ID.
-    pub fn delete_task(&self, id: &str) -> bool {
-        let mut tasks = self.tasks.lock().unwrap();
-        let len_before = tasks.len();
-        tasks.retain(|t| t.id != id);
-        tasks.len() < len_before
+    /// Set the default priority.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
     }
 
-    /// Get the count of tasks.
-    pub fn count(&self) -> usize {
-        let tasks = self.tasks.lock().unwrap();
-        tasks.len()
+    /// Set the default status.
+    pub fn status(mut self, status: Status) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Set a default metadata entry.
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Create a task with a generated ID from the template.
+    pub fn instantiate(&self, name: &str) -> Task {
+        let mut task = create_task(name, self.priority).with_status(self.status.clone());
+        task.metadata = self.metadata.clone();
+        task
     }
 }
 
-impl Default for TaskManager {
+impl Default for TaskTemplate {
     fn default() -> Self {
         Self::new()
     }
 }
 
-/// Validate a task.
-pub fn validate_task(task: &Task) -> Result<(), String> {
-    if task.id.is_empty() {
-        return Err("Task ID is required".to_string());
+/// Changes between two versions of a task. Metadata keys are sorted.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TaskDiff {
+    /// Changed fields among `name`, `status` and `priority`.
+    pub fields: Vec<&'static str>,
+    pub metadata_added: Vec<String>,
+    pub metadata_removed: Vec<String>,
+    pub metadata_changed: Vec<String>,
+}
+
+impl TaskDiff {
+    /// Check if nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+            && self.metadata_added.is_empty()
+            && self.metadata_removed.is_empty()
+            && self.metadata_changed.is_empty()
     }
-    if task.name.is_empty() {
-        return Err("Task name is required".to_string());
+}
+
+/// Tasks compare by their logical fields (id, name, status, priority and
+/// metadata). Audit timestamps such as `created_at` are deliberately ignored,
+/// so the same task recorded at different times still compares equal.
+impl PartialEq for Task {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+            && self.name == other.name
+            && self.status == other.status
+            && self.priority == other.priority
+            && self.metadata == other.metadata
     }
-    Ok(())
 }
 
-/// Create a new task with a generated ID.
-pub fn create_task(name: &str, priority: i32) -> Task {
-    let id = generate_id();
-    Task::new(id, name.to_string()).with_priority(priority)
+impl Eq for Task {}
+
+/// Tasks hash by `id` only: the id is the task's identity, and since equal
+/// tasks always share an id this stays consistent with `Eq`.
+///
+/// `Eq` compares the logical fields, so wrap tasks in `ById` to collapse
+/// versions of a task that share an id.
+impl Hash for Task {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
 }
 
-/// Generate a unique ID.
-fn generate_id() -> String {
-    use std::sync::atomic::{AtomicUsize, Ordering};
-    static COUNTER: AtomicUsize = AtomicUsize::new(0);
-    let id = COUNTER.fetch_add(1, Ordering::SeqCst);
-    format!("task-{}", id)
+/// Task with id-based `Eq` and `Hash`, so a `HashSet<ById>` keeps one entry per id.
+#[derive(Debug, Clone)]
+pub struct ById(pub Task);
+
+impl PartialEq for ById {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.id == other.0.id
+    }
 }
 
-/// Filter tasks by status.
-pub fn filter_by_status(tasks: &[Task], status: Status) -> Vec<Task> {
-    tasks.iter().filter(|t| t.status == status).cloned().collect()
+impl Eq for ById {}
+
+impl Hash for ById {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.id.hash(state);
+    }
 }
 
-/// Filter tasks by minimum priority.
-pub fn filter_by_priority(tasks: &[Task], min_priority: i32) -> Vec<Task> {
-    tasks.iter().filter(|t| t.priority >= min_priority).cloned().collect()
+impl Borrow<str> for ById {
+    fn borrow(&self) -> &str {
+        &self.0.id
+    }
 }
 
-/// Task processor for processing tasks.
-pub struct TaskProcessor {
-    manager: Arc<TaskManager>,
+/// Trait for entities with an ID.
+pub trait Entity {
+    fn get_id(&self) -> &str;
 }
 
-impl TaskProcessor {
-    /// Create a new task processor.
-    pub fn new(manager: Arc<TaskManager>) -> Self {
-        TaskProcessor { manager }
+impl Entity for Task {
+    fn get_id(&self) -> &str {
+        &self.id
     }
+}
 
-    /// Process all tasks.
-    pub fn process_all(&self) -> Vec<HashMap<String, String>> {
-        let tasks = self.manager.get_tasks::<fn(&Task) -> bool>(None);
-        tasks.iter().map(|t| self.process_task(t)).collect()
+/// Trait for values that can check their own invariants.
+///
+/// The default accepts everything, so types without rules can opt in with an
+/// empty `impl`.
+pub trait Validate {
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
     }
+}
 
-    /// Process only pending tasks.
-    pub fn process_pending(&self) -> Vec<HashMap<String, String>> {
-        let tasks = self.manager.get_tasks(Some(|t: &Task| t.status == Status::Pending));
-        tasks.iter().map(|t| self.process_task(t)).collect()
+impl Validate for Task {
+    fn validate(&self) -> Result<(), String> {
+        validate_task(self)
     }
+}
 
-    /// Process a single task.
-    fn process_task(&self, task: &Task) -> HashMap<String, String> {
-        format_task(task)
-    }
+/// Change notification emitted by a task manager.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskEvent {
+    Added(Task),
+    Updated(Task),
+    Deleted(String),
 }
 
-/// Format a task as a map.
-pub fn format_task(task: &Task) -> HashMap<String, String> {
-    let mut map = HashMap::new();
-    map.insert("id".to_string(), task.id.clone());
-    map.insert("name".to_string(), task.name.clone());
-    map.insert("status".to_string(), format!("{:?}", task.status));
-    map.insert("priority".to_string(), task.priority.to_string());
-    map
+/// What `expire_stale` does with expired tasks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExpiryPolicy {
+    /// Mark expired tasks as failed.
+    #[default]
+    Fail,
+    /// Remove expired tasks.
+    Delete,
 }
 
-/// Unused function for deadcode detection.
-#[allow(dead_code)]
-fn unused_function() -> &'static str {
-    "never used"
+/// Which task counts as next for `peek_next`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickOrder {
+    /// Oldest task first, as `pop_front` removes.
+    Fifo,
+    /// Highest priority first; ties go to the oldest task.
+    HighestPriority,
 }
 
-fn main() {
-    let manager = TaskManager::new();
-    let task = create_task("Test Task", 1);
-    manager.add_task(task).unwrap();
-    println!("Created {} tasks", manager.count());
+/// Point-in-time copy of a manager's tasks, used to roll back changes.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    tasks: Vec<Task>,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl Snapshot {
+    /// Get the captured tasks.
+    pub fn tasks(&self) -> &[Task] {
+        &self.tasks
+    }
+}
 
-    #[test]
-    fn test_create_task() {
-        let task = create_task("Test", 1);
-        assert!(!task.id.is_empty());
-        assert_eq!(task.name, "Test");
-        assert_eq!(task.priority, 1);
+/// One page of query results plus the metadata needed to render paging.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Matching items across all pages.
+    pub total: usize,
+    /// 1-based page number.
+    pub page: usize,
+    pub page_size: usize,
+    pub total_pages: usize,
+}
+
+/// Task manager that manages a collection of tasks.
+///
+/// The task count is mirrored in an atomic that every mutation refreshes while
+/// still holding the task lock, so `count()` never has to take the lock.
+pub struct TaskManager {
+    tasks: Arc<Mutex<TaskStore>>,
+    len: AtomicUsize,
+    subscribers: Arc<Mutex<Vec<Sender<TaskEvent>>>>,
+    expiry_policy: ExpiryPolicy,
+    slow_lock_threshold: Option<Duration>,
+    clock: Arc<dyn Clock>,
+    default_priority: i32,
+    aging_interval: Option<Duration>,
+    bloom: Option<CountingBloom>,
+    index: Option<Mutex<TextIndex>>,
+    wal: Option<Mutex<Wal>>,
+}
+
+impl TaskManager {
+    /// Create a new task manager.
+    pub fn new() -> Self {
+        TaskManager {
+            tasks: Arc::new(Mutex::new(TaskStore::new())),
+            len: AtomicUsize::new(0),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            expiry_policy: ExpiryPolicy::default(),
+            slow_lock_threshold: None,
+            clock: Arc::new(SystemClock),
+            default_priority: 1,
+            aging_interval: None,
+            bloom: None,
+            index: None,
+            wal: None,
+        }
     }
 
-    #[test]
-    fn test_task_manager() {
+    /// Read the current time from `clock` for expiry and claim leases.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Set the priority given to tasks created by `add_with_defaults`.
+    pub fn with_default_priority(mut self, priority: i32) -> Self {
+        self.default_priority = priority;
+        self
+    }
+
+    /// Raise a task's effective priority by one for every `interval` it has waited.
+    ///
+    /// Only ordering in `get_tasks_sorted` and `peek_next` is affected; stored
+    /// priorities are left alone.
+    pub fn with_aging(mut self, interval: Duration) -> Self {
+        self.aging_interval = Some(interval).filter(|i| !i.is_zero());
+        self
+    }
+
+    /// Track task IDs in a counting bloom filter sized for about `expected_tasks`.
+    ///
+    /// Enables the lock-free negative check in `probably_contains`.
+    pub fn with_bloom_filter(mut self, expected_tasks: usize) -> Self {
+        let bloom = CountingBloom::new(expected_tasks);
+        for task in self.lock_tasks().iter() {
+            bloom.insert(&task.id);
+        }
+        self.bloom = Some(bloom);
+        self
+    }
+
+    /// Append every mutation to the write-ahead log at `path`.
+    ///
+    /// Records are written under the task lock before the change is applied; if
+    /// the write fails the change is not applied. Methods touching several tasks
+    /// log one batch record.
+    pub fn with_wal(mut self, path: impl AsRef<Path>) -> io::Result<Self> {
+        self.wal = Some(Mutex::new(Wal::open(path.as_ref())?));
+        Ok(self)
+    }
+
+    /// Rebuild a manager by replaying the write-ahead log at `path`, then keep logging to it.
+    ///
+    /// A corrupt or partially written tail is truncated instead of failing recovery.
+    pub fn recover_from_wal(path: impl AsRef<Path>) -> io::Result<TaskManager> {
+        let path = path.as_ref();
+        let mut store = TaskStore::new();
+        for record in wal::recover(path)? {
+            replay(&mut store, record);
+        }
         let manager = TaskManager::new();
-        let task = create_task("Test", 1);
-        manager.add_task(task).unwrap();
-        assert_eq!(manager.count(), 1);
+        {
+            let mut tasks = manager.lock_tasks();
+            *tasks = store;
+            manager.track_reset(&tasks);
+            manager.sync_len(&tasks);
+        }
+        manager.with_wal(path)
+    }
+
+    /// Append the record built by `record` to the write-ahead log, if any.
+    ///
+    /// Call with the task lock held so records are logged in the order they apply.
+    fn log_wal(&self, record: impl FnOnce() -> WalRecord) -> Result<(), String> {
+        match &self.wal {
+            Some(wal) => wal.lock().unwrap().append(&record()).map_err(|e| {
+                log_at!(error, "WAL write failed: {}", e);
+                format!("WAL write failed: {}", e)
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Log the records built by `records` as one batch, skipping the write when there are none.
+    fn log_batch(&self, records: impl FnOnce() -> Vec<WalRecord>) -> Result<(), String> {
+        if self.wal.is_none() {
+            return Ok(());
+        }
+        let records = records();
+        if records.is_empty() {
+            return Ok(());
+        }
+        self.log_wal(|| WalRecord::Batch(records))
+    }
+
+    /// Maintain an inverted index over task names and metadata values for `search_index`.
+    pub fn with_index(mut self) -> Self {
+        let mut index = TextIndex::new();
+        for task in self.lock_tasks().iter() {
+            index.insert(&task);
+        }
+        self.index = Some(Mutex::new(index));
+        self
+    }
+
+    /// Get tasks with a name or metadata term starting with `term`, ignoring case, sorted by ID.
+    ///
+    /// Returns nothing unless the manager was built `with_index`.
+    pub fn search_index(&self, term: &str) -> Vec<Task> {
+        let Some(index) = &self.index else {
+            return Vec::new();
+        };
+        let tasks = self.lock_tasks();
+        let index = index.lock().unwrap();
+        let mut ids: Vec<&str> = index.search(term).into_iter().collect();
+        ids.sort_unstable();
+        ids.into_iter().filter_map(|id| tasks.get(id).map(Cow::into_owned)).collect()
+    }
+
+    /// Check if a task with `id` may exist, without taking the task lock.
+    ///
+    /// `false` means the task is definitely absent; `true` may be a false
+    /// positive, and is always returned when no bloom filter is configured.
+    pub fn probably_contains(&self, id: &str) -> bool {
+        self.bloom.as_ref().is_none_or(|b| b.probably_contains(id))
+    }
+
+    /// Share one allocation per distinct metadata key across all stored tasks.
+    ///
+    /// Tasks still read back with `String` keys; each read rebuilds the task's
+    /// metadata, trading some CPU for memory.
+    pub fn with_interning(self) -> Self {
+        self.lock_tasks().enable_interning();
+        self
+    }
+
+    /// Get the number of distinct interned metadata keys, or 0 without interning.
+    pub fn interned_key_count(&self) -> usize {
+        self.lock_tasks().interned_key_count()
+    }
+
+    /// Warn (with the `logging` feature) whenever the task lock is held longer than `threshold`.
+    pub fn with_slow_lock_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_lock_threshold = Some(threshold);
+        self
+    }
+
+    /// Acquire the task lock, timing how long it is held.
+    fn lock_tasks(&self) -> TimedGuard<'_> {
+        TimedGuard::new(self.tasks.lock().unwrap(), self.slow_lock_threshold)
+    }
+
+    /// Set how expired tasks are handled.
+    pub fn with_expiry_policy(mut self, policy: ExpiryPolicy) -> Self {
+        self.expiry_policy = policy;
+        self
+    }
+
+    /// Add a task to the manager.
+    ///
+    /// Fails if a task with the same ID is already stored, whatever the storage backend.
+    pub fn add_task(&self, task: Task) -> Result<(), String> {
+        if let Err(e) = validate_task(&task) {
+            log_at!(warn, "rejected task {:?}: {}", task.id, e);
+            return Err(e);
+        }
+        let mut tasks = self.lock_tasks();
+        if tasks.get(&task.id).is_some() {
+            return Err(format!("Task ID already exists: {}", task.id));
+        }
+        self.log_wal(|| WalRecord::Add(task.clone()))?;
+        self.track_added(&task);
+        tasks.push(task.clone());
+        self.sync_len(&tasks);
+        drop(tasks);
+        self.emit(TaskEvent::Added(task));
+        Ok(())
+    }
+
+    /// Create and add a task with a generated ID and the default priority.
+    pub fn add_with_defaults(&self, name: &str) -> Result<Task, String> {
+        let task = create_task(name, self.default_priority);
+        self.add_task(task.clone())?;
+        Ok(task)
+    }
+
+    /// Create and add a task from `template`.
+    pub fn add_from_template(&self, template: &TaskTemplate, name: &str) -> Result<Task, String> {
+        let task = template.instantiate(name);
+        self.add_task(task.clone())?;
+        Ok(task)
+    }
+
+    /// Get a task by ID.
+    pub fn get_task(&self, id: &str) -> Option<Task> {
+        let tasks = self.lock_tasks();
+        tasks.get(id).map(Cow::into_owned)
+    }
+
+    /// Get all tasks, optionally filtered.
+    pub fn get_tasks<F>(&self, filter: Option<F>) -> Vec<Task>
+    where
+        F: Fn(&Task) -> bool,
+    {
+        let tasks = self.lock_tasks();
+        match filter {
+            Some(f) => tasks.iter().filter(|t| f(t)).map(Cow::into_owned).collect(),
+            None => tasks.to_vec(),
+        }
+    }
+
+    /// Find tasks whose name is within `max_distance` edits of `query`.
+    ///
+    /// Names are compared case-insensitively by Levenshtein distance, and
+    /// results are sorted by ascending distance (ties keep insertion order).
+    #[cfg(feature = "fuzzy")]
+    pub fn fuzzy_search(&self, query: &str, max_distance: usize) -> Vec<Task> {
+        let query = query.to_lowercase();
+        let tasks = self.lock_tasks();
+        let mut matches: Vec<(usize, Task)> = tasks
+            .iter()
+            .map(|t| (strsim::levenshtein(&query, &t.name.to_lowercase()), t))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .map(|(distance, t)| (distance, t.into_owned()))
+            .collect();
+        matches.sort_by_key(|(distance, _)| *distance);
+        matches.into_iter().map(|(_, t)| t).collect()
+    }
+
+    /// Get the tasks matching a filter expression like `status=active AND priority>=5`.
+    ///
+    /// See the `query` module for the grammar.
+    pub fn query_str(&self, expr: &str) -> Result<Vec<Task>, String> {
+        let query = query::Query::parse(expr)?;
+        Ok(self.get_tasks(Some(|t: &Task| query.matches(t))))
+    }
+
+    /// Get one page of tasks, optionally filtered.
+    ///
+    /// `page` is 1-based and `page_size` is clamped to at least 1. `total`
+    /// counts filtered tasks before slicing, computed under the same lock as
+    /// `items`.
+    pub fn get_tasks_page<F>(&self, filter: Option<F>, page: usize, page_size: usize) -> Page<Task>
+    where
+        F: Fn(&Task) -> bool,
+    {
+        let page = page.max(1);
+        let page_size = page_size.max(1);
+        let tasks = self.lock_tasks();
+        let matching: Vec<Cow<Task>> = match &filter {
+            Some(f) => tasks.iter().filter(|t| f(t)).collect(),
+            None => tasks.iter().collect(),
+        };
+        let total = matching.len();
+        let items = matching
+            .into_iter()
+            .skip((page - 1) * page_size)
+            .take(page_size)
+            .map(Cow::into_owned)
+            .collect();
+        Page {
+            items,
+            total,
+            page,
+            page_size,
+            total_pages: total.div_ceil(page_size),
+        }
+    }
+
+    /// Convert every task into a custom output type.
+    pub fn project<R, F>(&self, f: F) -> Vec<R>
+    where
+        F: Fn(&Task) -> R,
+    {
+        let tasks = self.lock_tasks();
+        tasks.iter().map(|t| f(&t)).collect()
+    }
+
+    /// Count how many tasks have each metadata key.
+    pub fn metadata_key_frequency(&self) -> HashMap<String, usize> {
+        let tasks = self.lock_tasks();
+        let mut counts = HashMap::new();
+        for task in tasks.iter() {
+            for key in task.metadata.keys() {
+                *counts.entry(key.clone()).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// Update a task by ID.
+    ///
+    /// Returns `None` if the task is missing or the write-ahead log append fails;
+    /// the failure is logged as an error and the task is left unchanged.
+    pub fn update_task(&self, id: &str, name: Option<String>, status: Option<Status>) -> Option<Task> {
+        let mut tasks = self.lock_tasks();
+        let mut task = tasks.get_mut(id)?;
+        self.log_wal(|| WalRecord::Update {
+            id: id.to_string(),
+            name: name.clone(),
+            status: status.clone(),
+        })
+        .ok()?;
+        task.apply_update(name, status);
+        let updated = task.clone();
+        drop(task);
+        self.track_updated(&updated);
+        drop(tasks);
+        self.emit(TaskEvent::Updated(updated.clone()));
+        Some(updated)
+    }
+
+    /// Move every task in `from` to `to` under one lock, returning how many changed.
+    pub fn transition_all(&self, from: Status, to: Status) -> usize {
+        if from == to {
+            return 0;
+        }
+        let mut tasks = self.lock_tasks();
+        let logged = self.log_batch(|| {
+            tasks
+                .iter()
+                .filter(|t| t.status == from)
+                .map(|t| WalRecord::Update {
+                    id: t.id.clone(),
+                    name: None,
+                    status: Some(to.clone()),
+                })
+                .collect()
+        });
+        if logged.is_err() {
+            return 0;
+        }
+        let mut updated = Vec::new();
+        tasks.for_each_mut(|task| {
+            if task.status == from {
+                task.apply_update(None, Some(to.clone()));
+                updated.push(task.clone());
+            }
+        });
+        drop(tasks);
+        let count = updated.len();
+        for task in updated {
+            self.emit(TaskEvent::Updated(task));
+        }
+        count
+    }
+
+    /// Update a task by ID only if its version still matches `expected_version`.
+    ///
+    /// Returns `Err("version conflict")` when another update landed first.
+    pub fn update_task_versioned(
+        &self,
+        id: &str,
+        expected_version: u64,
+        name: Option<String>,
+        status: Option<Status>,
+    ) -> Result<Task, String> {
+        let mut tasks = self.lock_tasks();
+        let mut task = tasks.get_mut(id).ok_or_else(|| "Task not found".to_string())?;
+        if task.version != expected_version {
+            return Err("version conflict".to_string());
+        }
+        self.log_wal(|| WalRecord::Update {
+            id: id.to_string(),
+            name: name.clone(),
+            status: status.clone(),
+        })?;
+        task.apply_update(name, status);
+        let updated = task.clone();
+        drop(task);
+        self.track_updated(&updated);
+        drop(tasks);
+        self.emit(TaskEvent::Updated(updated.clone()));
+        Ok(updated)
+    }
+
+    /// Delete a task by ID.
+    pub fn delete_task(&self, id: &str) -> bool {
+        let mut tasks = self.lock_tasks();
+        if tasks.get(id).is_none() || self.log_wal(|| WalRecord::Delete(id.to_string())).is_err() {
+            return false;
+        }
+        let len_before = tasks.len();
+        tasks.retain(|t| t.id != id);
+        for _ in tasks.len()..len_before {
+            self.track_removed(id);
+        }
+        let deleted = tasks.len() < len_before;
+        self.sync_len(&tasks);
+        drop(tasks);
+        if deleted {
+            self.emit(TaskEvent::Deleted(id.to_string()));
+        }
+        deleted
+    }
+
+    /// Get a copy of the next task in `order` without removing it.
+    pub fn peek_next(&self, order: PickOrder) -> Option<Task> {
+        let tasks = self.lock_tasks();
+        let now = self.clock.system_now();
+        let next = match order {
+            PickOrder::Fifo => tasks.iter().next(),
+            PickOrder::HighestPriority => tasks
+                .iter()
+                .min_by_key(|t| std::cmp::Reverse(self.effective_priority(t, now))),
+        };
+        next.map(Cow::into_owned)
+    }
+
+    /// Get all tasks by descending effective priority; ties keep insertion order.
+    pub fn get_tasks_sorted(&self) -> Vec<Task> {
+        let now = self.clock.system_now();
+        let mut tasks: Vec<Task> = self.lock_tasks().to_vec();
+        tasks.sort_by_cached_key(|t| std::cmp::Reverse(self.effective_priority(t, now)));
+        tasks
+    }
+
+    /// Stored priority plus the aging boost earned by waiting since `created_at`.
+    fn effective_priority(&self, task: &Task, now: SystemTime) -> i64 {
+        let Some(interval) = self.aging_interval else {
+            return task.priority.into();
+        };
+        let waited = now.duration_since(task.created_at).unwrap_or_default();
+        let boost = i64::try_from(waited.as_nanos() / interval.as_nanos()).unwrap_or(i64::MAX);
+        i64::from(task.priority).saturating_add(boost)
+    }
+
+    /// Delete a task and every task transitively depending on it, returning the removed IDs.
+    ///
+    /// IDs come out breadth-first from `id`; dependency cycles are visited once.
+    pub fn delete_cascade(&self, id: &str) -> Vec<String> {
+        let mut tasks = self.lock_tasks();
+        if tasks.get(id).is_none() {
+            return Vec::new();
+        }
+        let stored: Vec<Cow<Task>> = tasks.iter().collect();
+        let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+        for task in &stored {
+            for dep in &task.depends_on {
+                dependents.entry(dep.as_str()).or_default().push(&task.id);
+            }
+        }
+        let mut removed = vec![id.to_string()];
+        let mut seen: HashSet<&str> = [id].into_iter().collect();
+        let mut next = 0;
+        while next < removed.len() {
+            for &child in dependents.get(removed[next].as_str()).into_iter().flatten() {
+                if seen.insert(child) {
+                    removed.push(child.to_string());
+                }
+            }
+            next += 1;
+        }
+        drop(dependents);
+        drop(stored);
+        if self.log_batch(|| removed.iter().cloned().map(WalRecord::Delete).collect()).is_err() {
+            return Vec::new();
+        }
+
+        let doomed: HashSet<&str> = removed.iter().map(String::as_str).collect();
+        tasks.retain(|t| {
+            let keep = !doomed.contains(t.id.as_str());
+            if !keep {
+                self.track_removed(&t.id);
+            }
+            keep
+        });
+        self.sync_len(&tasks);
+        drop(tasks);
+        for id in &removed {
+            self.emit(TaskEvent::Deleted(id.clone()));
+        }
+        removed
+    }
+
+    /// Remove and return the oldest task.
+    pub fn pop_front(&self) -> Option<Task> {
+        self.pop_with(|_| 0, TaskStore::pop_front)
+    }
+
+    /// Remove and return the newest task.
+    pub fn pop_back(&self) -> Option<Task> {
+        self.pop_with(|len| len.saturating_sub(1), TaskStore::pop_back)
+    }
+
+    /// Remove the task at `position(len)` with `pop` under the lock, keeping the count and
+    /// filter in sync.
+    fn pop_with(
+        &self,
+        position: fn(usize) -> usize,
+        pop: fn(&mut TaskStore) -> Option<Task>,
+    ) -> Option<Task> {
+        let mut tasks = self.lock_tasks();
+        let id = tasks.get_index(position(tasks.len()))?.id.clone();
+        self.log_wal(|| WalRecord::Delete(id)).ok()?;
+        let task = pop(&mut tasks)?;
+        self.track_removed(&task.id);
+        self.sync_len(&tasks);
+        drop(tasks);
+        self.emit(TaskEvent::Deleted(task.id.clone()));
+        Some(task)
+    }
+
+    /// Remove and return all tasks in insertion order.
+    pub fn drain(&self) -> Vec<Task> {
+        let mut tasks = self.lock_tasks();
+        if self.log_wal(|| WalRecord::Clear).is_err() {
+            return Vec::new();
+        }
+        let drained = tasks.drain();
+        self.track_reset(&tasks);
+        self.sync_len(&tasks);
+        drop(tasks);
+        for task in &drained {
+            self.emit(TaskEvent::Deleted(task.id.clone()));
+        }
+        drained
+    }
+
+    /// Remove all tasks.
+    pub fn clear(&self) {
+        let mut tasks = self.lock_tasks();
+        if self.log_wal(|| WalRecord::Clear).is_err() {
+            return;
+        }
+        let removed: Vec<String> = tasks.iter().map(|t| t.id.clone()).collect();
+        tasks.clear();
+        self.track_reset(&tasks);
+        self.sync_len(&tasks);
+        drop(tasks);
+        for id in removed {
+            self.emit(TaskEvent::Deleted(id));
+        }
+    }
+
+    /// Get the count of tasks without taking the task lock.
+    pub fn count(&self) -> usize {
+        self.len.load(Ordering::SeqCst)
+    }
+
+    /// Record a newly stored task in the bloom filter and text index; call with the task lock held.
+    fn track_added(&self, task: &Task) {
+        if let Some(bloom) = &self.bloom {
+            bloom.insert(&task.id);
+        }
+        self.track_updated(task);
+    }
+
+    /// Reindex a task whose fields changed; call with the task lock held.
+    fn track_updated(&self, task: &Task) {
+        if let Some(index) = &self.index {
+            index.lock().unwrap().insert(task);
+        }
+    }
+
+    /// Forget one removed task; call with the task lock held.
+    fn track_removed(&self, id: &str) {
+        if let Some(bloom) = &self.bloom {
+            bloom.remove(id);
+        }
+        if let Some(index) = &self.index {
+            index.lock().unwrap().remove(id);
+        }
+    }
+
+    /// Rebuild the bloom filter and text index from `tasks`; call with the task lock held.
+    fn track_reset(&self, tasks: &TaskStore) {
+        if let Some(bloom) = &self.bloom {
+            bloom.clear();
+            for task in tasks.iter() {
+                bloom.insert(&task.id);
+            }
+        }
+        if let Some(index) = &self.index {
+            let mut index = index.lock().unwrap();
+            index.clear();
+            for task in tasks.iter() {
+                index.insert(&task);
+            }
+        }
+    }
+
+    /// Refresh the cached count; call with the task lock held.
+    fn sync_len(&self, tasks: &TaskStore) {
+        self.len.store(tasks.len(), Ordering::SeqCst);
+    }
+
+    /// Create an independent copy of this manager.
+    ///
+    /// Unlike cloning the shared `Arc`, the copy owns its own task storage,
+    /// so mutations on either manager are not visible to the other.
+    pub fn deep_clone(&self) -> TaskManager {
+        let tasks = self.lock_tasks();
+        TaskManager {
+            tasks: Arc::new(Mutex::new(tasks.clone())),
+            len: AtomicUsize::new(tasks.len()),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            expiry_policy: self.expiry_policy,
+            slow_lock_threshold: self.slow_lock_threshold,
+            clock: self.clock.clone(),
+            default_priority: self.default_priority,
+            aging_interval: self.aging_interval,
+            bloom: self.bloom.clone(),
+            index: self.index.as_ref().map(|i| Mutex::new(i.lock().unwrap().clone())),
+            // The copy must not write into this manager's log.
+            wal: None,
+        }
+    }
+
+    /// Fail or delete pending tasks past their expiry, returning their IDs.
+    ///
+    /// Tasks without an expiry are never touched.
+    pub fn expire_stale(&self) -> Vec<String> {
+        let now = self.clock.system_now();
+        let mut tasks = self.lock_tasks();
+        let logged = self.log_batch(|| {
+            let stale = tasks.iter().filter(|t| t.is_expired(now)).map(|t| t.id.clone());
+            match self.expiry_policy {
+                ExpiryPolicy::Fail => stale
+                    .map(|id| WalRecord::Update {
+                        id,
+                        name: None,
+                        status: Some(Status::Failed),
+                    })
+                    .collect(),
+                ExpiryPolicy::Delete => stale.map(WalRecord::Delete).collect(),
+            }
+        });
+        if logged.is_err() {
+            return Vec::new();
+        }
+        let mut expired = Vec::new();
+        let mut events = Vec::new();
+        match self.expiry_policy {
+            ExpiryPolicy::Fail => {
+                tasks.for_each_mut(|task| {
+                    if task.is_expired(now) {
+                        task.apply_update(None, Some(Status::Failed));
+                        expired.push(task.id.clone());
+                        events.push(TaskEvent::Updated(task.clone()));
+                    }
+                });
+            }
+            ExpiryPolicy::Delete => {
+                tasks.retain(|t| {
+                    if !t.is_expired(now) {
+                        return true;
+                    }
+                    self.track_removed(&t.id);
+                    expired.push(t.id.clone());
+                    events.push(TaskEvent::Deleted(t.id.clone()));
+                    false
+                });
+            }
+        }
+        self.sync_len(&tasks);
+        drop(tasks);
+        for event in events {
+            self.emit(event);
+        }
+        expired
+    }
+
+    /// Get the fraction of tasks that are completed, or 0.0 when there are none.
+    pub fn completion_ratio(&self) -> f64 {
+        let tasks = self.lock_tasks();
+        if tasks.is_empty() {
+            return 0.0;
+        }
+        let completed = tasks.iter().filter(|t| t.is_complete()).count();
+        completed as f64 / tasks.len() as f64
+    }
+
+    /// Get incomplete tasks whose deadline has passed.
+    pub fn breached_deadlines(&self) -> Vec<Task> {
+        let now = self.clock.system_now();
+        let tasks = self.lock_tasks();
+        tasks.iter().filter(|t| t.is_breached(now)).map(Cow::into_owned).collect()
+    }
+
+    /// Pick a pending task at random, weighted by priority.
+    ///
+    /// Non-positive priorities count as weight 1 so low-priority work is never
+    /// starved completely.
+    pub fn pick_weighted(&self, rng: &mut impl Rng) -> Option<Task> {
+        let tasks = self.lock_tasks();
+        let pending: Vec<Cow<Task>> =
+            tasks.iter().filter(|t| t.status == Status::Pending).collect();
+        let weight = |t: &Task| t.priority.max(1) as u64;
+        let total: u64 = pending.iter().map(|t| weight(t)).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = rng.gen_below(total);
+        for task in pending {
+            let w = weight(&task);
+            if roll < w {
+                return Some(task.into_owned());
+            }
+            roll -= w;
+        }
+        None
+    }
+
+    /// Pick `n` distinct tasks uniformly at random, or all of them if `n` exceeds the count.
+    ///
+    /// Uses reservoir sampling under the lock, so only the chosen tasks are cloned.
+    pub fn sample(&self, n: usize, rng: &mut impl Rng) -> Vec<Task> {
+        let tasks = self.lock_tasks();
+        let mut reservoir: Vec<Cow<Task>> = Vec::with_capacity(n.min(tasks.len()));
+        for (seen, task) in tasks.iter().enumerate() {
+            if seen < n {
+                reservoir.push(task);
+            } else {
+                let slot = rng.gen_below(seen as u64 + 1) as usize;
+                if slot < n {
+                    reservoir[slot] = task;
+                }
+            }
+        }
+        reservoir.into_iter().map(Cow::into_owned).collect()
+    }
+
+    /// Claim the first pending task for `worker_id` for the length of `lease`.
+    ///
+    /// The task moves to `Status::Claimed` under the lock, so concurrent
+    /// workers never receive the same task.
+    pub fn claim_next(&self, worker_id: &str, lease: Duration) -> Option<Task> {
+        let mut tasks = self.lock_tasks();
+        let mut claimed = tasks.iter().find(|t| t.status == Status::Pending)?.into_owned();
+        claimed.apply_update(None, Some(Status::Claimed));
+        claimed.claim = Some(Claim {
+            worker_id: worker_id.to_string(),
+            expires_at: self.clock.system_now() + lease,
+        });
+        self.log_wal(|| WalRecord::Put(claimed.clone())).ok()?;
+        tasks.put(claimed.clone());
+        drop(tasks);
+        self.emit(TaskEvent::Updated(claimed.clone()));
+        Some(claimed)
+    }
+
+    /// Return claimed tasks whose lease has run out to pending, returning their IDs.
+    pub fn release_expired_claims(&self) -> Vec<String> {
+        let now = self.clock.system_now();
+        let lapsed = |task: &Task| {
+            task.status == Status::Claimed
+                && task.claim.as_ref().is_some_and(|c| c.expires_at <= now)
+        };
+        let mut tasks = self.lock_tasks();
+        let updated: Vec<Task> = tasks
+            .iter()
+            .filter(|t| lapsed(t))
+            .map(|t| {
+                let mut task = t.into_owned();
+                task.claim = None;
+                task.apply_update(None, Some(Status::Pending));
+                task
+            })
+            .collect();
+        if self.log_batch(|| updated.iter().cloned().map(WalRecord::Put).collect()).is_err() {
+            return Vec::new();
+        }
+        let mut replacements = updated.iter();
+        tasks.for_each_mut(|task| {
+            if lapsed(task) {
+                if let Some(new) = replacements.next() {
+                    *task = new.clone();
+                }
+            }
+        });
+        drop(tasks);
+        let released = updated.iter().map(|t| t.id.clone()).collect();
+        for task in updated {
+            self.emit(TaskEvent::Updated(task));
+        }
+        released
+    }
+
+    /// Capture the current task set.
+    pub fn snapshot(&self) -> Snapshot {
+        let tasks = self.lock_tasks();
+        Snapshot { tasks: tasks.to_vec() }
+    }
+
+    /// Replace the current task set with a previously captured snapshot.
+    ///
+    /// Subscribers see `Deleted` for every replaced task, then `Added` for every
+    /// restored one. Nothing changes if the write-ahead log can't record the restore.
+    pub fn restore(&self, snapshot: Snapshot) {
+        let mut tasks = self.lock_tasks();
+        let logged = self.log_wal(|| {
+            let adds = snapshot.tasks.iter().cloned().map(WalRecord::Add);
+            WalRecord::Batch(std::iter::once(WalRecord::Clear).chain(adds).collect())
+        });
+        if logged.is_err() {
+            return;
+        }
+        let removed: Vec<String> = tasks.iter().map(|t| t.id.clone()).collect();
+        tasks.clear();
+        tasks.extend(snapshot.tasks.iter().cloned());
+        self.track_reset(&tasks);
+        self.sync_len(&tasks);
+        drop(tasks);
+        for id in removed {
+            self.emit(TaskEvent::Deleted(id));
+        }
+        for task in snapshot.tasks {
+            self.emit(TaskEvent::Added(task));
+        }
+    }
+
+    /// Export tasks as CSV with an `id,name,status,priority` header, in insertion order.
+    pub fn export_csv(&self) -> String {
+        let mut out = Vec::new();
+        self.export_csv_to(&mut out).expect("writing to a Vec cannot fail");
+        String::from_utf8(out).expect("CSV output is UTF-8")
+    }
+
+    /// Stream `export_csv` output to `writer` one row at a time.
+    pub fn export_csv_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "{}", CSV_HEADER.join(","))?;
+        for task in self.stream_tasks() {
+            let status = format!("{:?}", task.status);
+            let priority = task.priority.to_string();
+            writeln!(writer, "{}", csv::write_record(&[&task.id, &task.name, &status, &priority]))?;
+        }
+        Ok(())
+    }
+
+    /// Export tasks as a JSON array of objects, in insertion order.
+    pub fn export_json(&self) -> String {
+        let mut out = Vec::new();
+        self.export_json_to(&mut out).expect("writing to a Vec cannot fail");
+        String::from_utf8(out).expect("JSON output is UTF-8")
+    }
+
+    /// Stream `export_json` output to `writer` one element at a time.
+    pub fn export_json_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(b"[")?;
+        for (i, task) in self.stream_tasks().enumerate() {
+            if i > 0 {
+                writer.write_all(b",")?;
+            }
+            writer.write_all(task_to_json(&task).as_bytes())?;
+        }
+        writer.write_all(b"]")
+    }
+
+    /// Iterate over copies of the current tasks without holding the lock between chunks.
+    ///
+    /// The lock is taken once to snapshot ids, then once per `STREAM_CHUNK` ids to copy
+    /// those tasks, so tasks deleted mid-stream are skipped and no surviving one is missed.
+    /// Lookups start at the id's snapshot position, staying O(1) unless the store shifts.
+    fn stream_tasks(&self) -> impl Iterator<Item = Task> + '_ {
+        let ids: Vec<String> = self.lock_tasks().ids().map(String::from).collect();
+        let mut next = 0;
+        let mut shift = 0;
+        std::iter::from_fn(move || {
+            let chunk = &ids[next..ids.len().min(next + STREAM_CHUNK)];
+            if chunk.is_empty() {
+                return None;
+            }
+            let tasks = self.lock_tasks();
+            let mut copied = Vec::with_capacity(chunk.len());
+            for (position, id) in (next..).zip(chunk) {
+                if let Some((found, task)) = tasks.find_near(id, position.saturating_sub(shift)) {
+                    shift = position.saturating_sub(found);
+                    copied.push(task.into_owned());
+                }
+            }
+            next += chunk.len();
+            Some(copied)
+        })
+        .flatten()
+    }
+
+    /// Import tasks from CSV written by `export_csv`, returning how many were added.
+    ///
+    /// Every row is parsed and validated first; on any error nothing is added.
+    pub fn import_csv(&self, csv: &str) -> Result<usize, String> {
+        let mut records = csv::parse_records(csv)?.into_iter();
+        if records.next().unwrap_or_default() != CSV_HEADER {
+            return Err(format!("CSV header must be '{}'", CSV_HEADER.join(",")));
+        }
+        let mut imported = Vec::new();
+        for (i, record) in records.enumerate() {
+            let line = i + 2;
+            let [id, name, status, priority] = <[String; 4]>::try_from(record)
+                .map_err(|r| format!("Line {}: expected 4 fields, found {}", line, r.len()))?;
+            let status = status.parse().map_err(|e| format!("Line {}: {}", line, e))?;
+            let priority = priority
+                .parse()
+                .map_err(|_| format!("Line {}: invalid priority '{}'", line, priority))?;
+            let task = Task::new(id, name).with_status(status).with_priority(priority);
+            validate_task(&task).map_err(|e| format!("Line {}: {}", line, e))?;
+            imported.push(task);
+        }
+        self.insert_all(imported)
+    }
+
+    /// Export tasks as JSON preceded by a `crc32:<hex>` checksum line.
+    pub fn export_json_with_checksum(&self) -> String {
+        let json = self.export_json();
+        format!("{}{:08x}\n{}", CHECKSUM_PREFIX, checksum::crc32(json.as_bytes()), json)
+    }
+
+    /// Import the output of `export_json_with_checksum`, rejecting corrupted data.
+    ///
+    /// Fails with `"checksum mismatch"` if the JSON no longer matches its checksum.
+    #[cfg(feature = "json")]
+    pub fn import_json_verified(&self, data: &str) -> Result<usize, String> {
+        let (header, json) = data.split_once('\n').ok_or("Missing checksum line")?;
+        let expected = header
+            .strip_prefix(CHECKSUM_PREFIX)
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or("Invalid checksum line")?;
+        if checksum::crc32(json.as_bytes()) != expected {
+            return Err("checksum mismatch".to_string());
+        }
+        self.import_json(json)
+    }
+
+    /// Import tasks from JSON written by `export_json`, returning how many were added.
+    ///
+    /// Every element is parsed and validated first; on any error nothing is added.
+    #[cfg(feature = "json")]
+    pub fn import_json(&self, json: &str) -> Result<usize, String> {
+        let value: serde_json::Value = json.parse().map_err(|e| format!("Invalid JSON: {}", e))?;
+        let items = value.as_array().ok_or("JSON export must be an array")?;
+        let mut imported = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            let field = |key: &str| {
+                item.get(key)
+                    .and_then(|v| v.as_str())
+                    .ok_or(format!("Element {}: missing string '{}'", i, key))
+            };
+            let mut task = Task::new(field("id")?.to_string(), field("name")?.to_string())
+                .with_status(field("status")?.parse().map_err(|e| format!("Element {}: {}", i, e))?);
+            task.priority = item
+                .get("priority")
+                .and_then(|v| v.as_i64())
+                .and_then(|p| i32::try_from(p).ok())
+                .ok_or(format!("Element {}: invalid priority", i))?;
+            if let Some(metadata) = item.get("metadata").and_then(|m| m.as_object()) {
+                for (key, value) in metadata {
+                    let value = value
+                        .as_str()
+                        .ok_or(format!("Element {}: metadata '{}' must be a string", i, key))?;
+                    task.metadata.insert(key.clone(), value.to_string());
+                }
+            }
+            validate_task(&task).map_err(|e| format!("Element {}: {}", i, e))?;
+            imported.push(task);
+        }
+        self.insert_all(imported)
+    }
+
+    /// Export tasks as gzip-compressed JSON.
+    #[cfg(feature = "compression")]
+    pub fn export_json_gz(&self) -> Result<Vec<u8>, String> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        self.export_json_to(&mut encoder).map_err(|e| e.to_string())?;
+        encoder.finish().map_err(|e| e.to_string())
+    }
+
+    /// Import tasks from gzip-compressed JSON written by `export_json_gz`.
+    #[cfg(feature = "compression")]
+    pub fn import_json_gz(&self, bytes: &[u8]) -> Result<usize, String> {
+        let mut json = String::new();
+        io::Read::read_to_string(&mut flate2::read::GzDecoder::new(bytes), &mut json)
+            .map_err(|e| format!("Invalid gzip data: {}", e))?;
+        self.import_json(&json)
+    }
+
+    /// Export all tasks in `format`.
+    #[cfg(any(feature = "json", feature = "yaml", feature = "msgpack"))]
+    pub fn export(&self, format: Format) -> Result<Vec<u8>, String> {
+        match format {
+            #[cfg(feature = "json")]
+            Format::Json => Ok(self.export_json().into_bytes()),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => serde_yaml::to_string(&self.task_records())
+                .map(String::into_bytes)
+                .map_err(|e| e.to_string()),
+            #[cfg(feature = "msgpack")]
+            Format::MsgPack => {
+                rmp_serde::to_vec_named(&self.task_records()).map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    /// Import tasks written by `export` in the same `format`.
+    ///
+    /// As with `import_json`, nothing is added unless every task is valid.
+    #[cfg(any(feature = "json", feature = "yaml", feature = "msgpack"))]
+    pub fn import(&self, format: Format, bytes: &[u8]) -> Result<usize, String> {
+        match format {
+            #[cfg(feature = "json")]
+            Format::Json => {
+                let json = std::str::from_utf8(bytes).map_err(|e| format!("Invalid UTF-8: {}", e))?;
+                self.import_json(json)
+            }
+            #[cfg(feature = "yaml")]
+            Format::Yaml => self.import_records(
+                serde_yaml::from_slice(bytes).map_err(|e| format!("Invalid YAML: {}", e))?,
+            ),
+            #[cfg(feature = "msgpack")]
+            Format::MsgPack => self.import_records(
+                rmp_serde::from_slice(bytes).map_err(|e| format!("Invalid MessagePack: {}", e))?,
+            ),
+        }
+    }
+
+    /// Snapshot tasks as serde records in insertion order.
+    #[cfg(any(feature = "yaml", feature = "msgpack"))]
+    fn task_records(&self) -> Vec<format::TaskRecord> {
+        self.lock_tasks().iter().map(|t| format::TaskRecord::from(&*t)).collect()
+    }
+
+    /// Validate every record, then add them all.
+    #[cfg(any(feature = "yaml", feature = "msgpack"))]
+    fn import_records(&self, records: Vec<format::TaskRecord>) -> Result<usize, String> {
+        let imported = records
+            .into_iter()
+            .enumerate()
+            .map(|(i, record)| record.into_task().map_err(|e| format!("Element {}: {}", i, e)))
+            .collect::<Result<Vec<Task>, String>>()?;
+        self.insert_all(imported)
+    }
+
+    /// Add already validated tasks under one lock, returning how many were added.
+    ///
+    /// Adds nothing if any ID is already stored or repeated in `imported`.
+    fn insert_all(&self, imported: Vec<Task>) -> Result<usize, String> {
+        let mut tasks = self.lock_tasks();
+        let mut ids: HashSet<&str> = tasks.ids().collect();
+        if let Some(task) = imported.iter().find(|t| !ids.insert(&t.id)) {
+            return Err(format!("Task ID already exists: {}", task.id));
+        }
+        drop(ids);
+        self.log_batch(|| imported.iter().cloned().map(WalRecord::Add).collect())?;
+        for task in &imported {
+            self.track_added(task);
+            tasks.push(task.clone());
+        }
+        self.sync_len(&tasks);
+        drop(tasks);
+        let count = imported.len();
+        for task in imported {
+            self.emit(TaskEvent::Added(task));
+        }
+        Ok(count)
+    }
+
+    /// Subscribe to task change events.
+    ///
+    /// Each subscriber gets its own unbounded channel, so mutations never block
+    /// on a slow consumer; undrained events simply queue up in the receiver.
+    /// Dropped receivers are pruned on the next event.
+    pub fn subscribe(&self) -> Receiver<TaskEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Subscribe with batched delivery; see `CoalescingSink` for flush and cancel rules.
+    ///
+    /// Batching stops, after a final flush, when the returned sink is dropped.
+    pub fn subscribe_batched<F>(
+        &self,
+        max_batch: usize,
+        interval: Duration,
+        callback: F,
+    ) -> CoalescingSink
+    where
+        F: FnMut(Vec<TaskEvent>) + Send + 'static,
+    {
+        CoalescingSink::spawn(self.subscribe(), max_batch, interval, callback)
+    }
+
+    /// Send an event to every live subscriber.
+    fn emit(&self, event: TaskEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
+
+/// Columns written by `TaskManager::export_csv`.
+const CSV_HEADER: [&str; 4] = ["id", "name", "status", "priority"];
+
+/// Start of the checksum line written by `export_json_with_checksum`.
+const CHECKSUM_PREFIX: &str = "crc32:";
+
+/// Tasks copied per lock acquisition while streaming an export.
+const STREAM_CHUNK: usize = 256;
+
+/// Apply one recovered write-ahead log record to `tasks`.
+fn replay(tasks: &mut TaskStore, record: WalRecord) {
+    match record {
+        WalRecord::Add(task) => tasks.push(task),
+        WalRecord::Put(task) => tasks.put(task),
+        WalRecord::Update { id, name, status } => {
+            if let Some(mut task) = tasks.get_mut(&id) {
+                task.apply_update(name, status);
+            }
+        }
+        WalRecord::Delete(id) => tasks.retain(|t| t.id != id),
+        WalRecord::Clear => tasks.clear(),
+        WalRecord::Batch(records) => {
+            for record in records {
+                replay(tasks, record);
+            }
+        }
+    }
+}
+
+impl Default for TaskManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Validate a task.
+pub fn validate_task(task: &Task) -> Result<(), String> {
+    if task.id.is_empty() {
+        return Err("Task ID is required".to_string());
+    }
+    if task.name.is_empty() {
+        return Err("Task name is required".to_string());
+    }
+    Ok(())
+}
+
+/// Extra validation rules applied on top of `validate_task`.
+#[derive(Debug, Clone, Default)]
+pub struct TaskSchema {
+    /// Metadata keys that must be present with a non-empty value.
+    pub required_metadata: Vec<String>,
+}
+
+/// Validate a task against a schema, reporting the first failure.
+pub fn validate_task_with_schema(task: &Task, schema: &TaskSchema) -> Result<(), String> {
+    validate_task(task)?;
+    first_error(schema_errors(task, schema))
+}
+
+/// Collect every schema failure in `required_metadata` order.
+fn schema_errors(task: &Task, schema: &TaskSchema) -> Vec<String> {
+    let mut errors = Vec::new();
+    for key in &schema.required_metadata {
+        match task.metadata.get(key) {
+            None => errors.push(format!("Metadata '{}' is required", key)),
+            Some(value) if value.is_empty() => errors.push(format!("Metadata '{}' must not be empty", key)),
+            Some(_) => {}
+        }
+    }
+    errors
+}
+
+/// Rules for task names applied on top of `validate_task`.
+///
+/// The default policy adds nothing beyond `validate_task`'s non-empty check.
+#[derive(Debug, Clone)]
+pub struct NamePolicy {
+    /// Maximum name length in characters.
+    pub max_len: Option<usize>,
+    /// Whether control characters such as newlines are allowed.
+    pub allow_control: bool,
+}
+
+impl Default for NamePolicy {
+    fn default() -> Self {
+        NamePolicy {
+            max_len: None,
+            allow_control: true,
+        }
+    }
+}
+
+/// Validate a task's name against a policy, reporting the first failed rule.
+pub fn validate_task_with_policy(task: &Task, policy: &NamePolicy) -> Result<(), String> {
+    validate_task(task)?;
+    first_error(policy_errors(task, policy))
+}
+
+/// Collect every name policy failure.
+fn policy_errors(task: &Task, policy: &NamePolicy) -> Vec<String> {
+    let mut errors = Vec::new();
+    if let Some(max_len) = policy.max_len {
+        let len = task.name.chars().count();
+        if len > max_len {
+            errors.push(format!("Task name is {} characters, max is {}", len, max_len));
+        }
+    }
+    if !policy.allow_control {
+        if let Some(c) = task.name.chars().find(|c| c.is_control()) {
+            errors.push(format!("Task name contains control character {:?}", c));
+        }
+    }
+    errors
+}
+
+/// Turn a list of failures into its first error.
+fn first_error(errors: Vec<String>) -> Result<(), String> {
+    match errors.into_iter().next() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Validate a task, reporting every basic failure instead of only the first.
+pub fn validate_task_all(task: &Task) -> Result<(), Vec<String>> {
+    validate_task_all_with(task, None, None)
+}
+
+/// Validate a task against the basic rules plus an optional schema and name policy,
+/// reporting every failure.
+pub fn validate_task_all_with(
+    task: &Task,
+    schema: Option<&TaskSchema>,
+    policy: Option<&NamePolicy>,
+) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    if task.id.is_empty() {
+        errors.push("Task ID is required".to_string());
+    }
+    if task.name.is_empty() {
+        errors.push("Task name is required".to_string());
+    }
+    if let Some(schema) = schema {
+        errors.extend(schema_errors(task, schema));
+    }
+    if let Some(policy) = policy {
+        errors.extend(policy_errors(task, policy));
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Create a new task with a generated ID.
+pub fn create_task(name: &str, priority: i32) -> Task {
+    let id = generate_id();
+    Task::new(id, name.to_string()).with_priority(priority)
+}
+
+/// Generate a unique ID.
+fn generate_id() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+    format!("task-{}", id)
+}
+
+/// Filter tasks by status.
+pub fn filter_by_status(tasks: &[Task], status: Status) -> Vec<Task> {
+    tasks.iter().filter(|t| t.status == status).cloned().collect()
+}
+
+/// Filter tasks whose status is any of `statuses`.
+pub fn filter_by_status_any(tasks: &[Task], statuses: &[Status]) -> Vec<Task> {
+    tasks.iter().filter(|t| statuses.contains(&t.status)).cloned().collect()
+}
+
+/// Filter tasks by minimum priority.
+pub fn filter_by_priority(tasks: &[Task], min_priority: i32) -> Vec<Task> {
+    tasks.iter().filter(|t| t.priority >= min_priority).cloned().collect()
+}
+
+/// Convert tasks into a custom output type.
+pub fn project_tasks<R>(tasks: &[Task], f: impl Fn(&Task) -> R) -> Vec<R> {
+    tasks.iter().map(f).collect()
+}
+
+/// Fallible formatter used by a task processor.
+pub type Formatter = Box<dyn Fn(&Task) -> Result<HashMap<String, String>, String> + Send + Sync>;
+
+/// Progress callback invoked with `(processed, total)` after each task.
+pub type ProgressCallback = Box<dyn Fn(usize, usize) + Send + Sync>;
+
+/// Outcome of `TaskProcessor::shutdown` for the tasks in flight when it was called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Tasks that finished within the timeout.
+    pub completed: usize,
+    /// Tasks still running when the timeout ran out.
+    pub abandoned: usize,
+}
+
+/// In-flight bookkeeping shared by processing threads and `shutdown`.
+#[derive(Debug, Default)]
+struct DrainState {
+    shutting_down: bool,
+    in_flight: usize,
+    finished: usize,
+}
+
+/// Task processor for processing tasks.
+///
+/// After `shutdown`, tasks not yet started are skipped and reported with an
+/// `error` instead of being formatted.
+pub struct TaskProcessor {
+    manager: Arc<TaskManager>,
+    retries: u32,
+    concurrency: usize,
+    formatter: Formatter,
+    on_progress: Option<ProgressCallback>,
+    smoothing: f64,
+    avg_processing: Mutex<Option<Duration>>,
+    drain: Mutex<DrainState>,
+    idle: Condvar,
+}
+
+impl TaskProcessor {
+    /// Create a new task processor.
+    pub fn new(manager: Arc<TaskManager>) -> Self {
+        TaskProcessorBuilder::new().build(manager)
+    }
+
+    /// Start configuring a task processor.
+    pub fn builder() -> TaskProcessorBuilder {
+        TaskProcessorBuilder::new()
+    }
+
+    /// Get the exponential moving average of per-task processing time, if any task ran.
+    pub fn avg_processing_time(&self) -> Option<Duration> {
+        *self.avg_processing.lock().unwrap()
+    }
+
+    /// Forget the processing time average.
+    pub fn reset_stats(&self) {
+        *self.avg_processing.lock().unwrap() = None;
+    }
+
+    /// Fold one processing duration into the moving average.
+    fn record_duration(&self, elapsed: Duration) {
+        let mut avg = self.avg_processing.lock().unwrap();
+        *avg = Some(match *avg {
+            None => elapsed,
+            Some(prev) => {
+                let alpha = self.smoothing;
+                Duration::from_secs_f64(alpha * elapsed.as_secs_f64() + (1.0 - alpha) * prev.as_secs_f64())
+            }
+        });
+    }
+
+    /// Stop starting new tasks and wait up to `timeout` for in-flight ones to finish.
+    ///
+    /// Abandoned tasks keep running on their threads; their results are still
+    /// returned by the call that started them, but `shutdown` no longer waits.
+    pub fn shutdown(&self, timeout: Duration) -> ShutdownReport {
+        let mut drain = self.drain.lock().unwrap();
+        drain.shutting_down = true;
+        let finished_before = drain.finished;
+        let (drain, _) = self
+            .idle
+            .wait_timeout_while(drain, timeout, |d| d.in_flight > 0)
+            .unwrap();
+        let report = ShutdownReport {
+            completed: drain.finished - finished_before,
+            abandoned: drain.in_flight,
+        };
+        log_at!(info, "task processor shut down: {:?}", report);
+        report
+    }
+
+    /// Check whether `shutdown` has been called.
+    pub fn is_shut_down(&self) -> bool {
+        self.drain.lock().unwrap().shutting_down
+    }
+
+    /// Process all tasks.
+    pub fn process_all(&self) -> Vec<HashMap<String, String>> {
+        let tasks = self.manager.get_tasks::<fn(&Task) -> bool>(None);
+        self.process_batch(&tasks)
+    }
+
+    /// Process only pending tasks.
+    pub fn process_pending(&self) -> Vec<HashMap<String, String>> {
+        let tasks = self.manager.get_tasks(Some(|t: &Task| t.status == Status::Pending));
+        self.process_batch(&tasks)
+    }
+
+    /// Process tasks across the configured number of threads, keeping input order.
+    fn process_batch(&self, tasks: &[Task]) -> Vec<HashMap<String, String>> {
+        let total = tasks.len();
+        let processed = AtomicUsize::new(0);
+        let run = |task: &Task| {
+            let result = self.process_task(task);
+            let done = processed.fetch_add(1, Ordering::SeqCst) + 1;
+            if let Some(cb) = &self.on_progress {
+                cb(done, total);
+            }
+            result
+        };
+
+        if self.concurrency <= 1 || total <= 1 {
+            return tasks.iter().map(run).collect();
+        }
+        let chunk_size = total.div_ceil(self.concurrency);
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = tasks
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(|| chunk.iter().map(run).collect::<Vec<_>>()))
+                .collect();
+            handles.into_iter().flat_map(|h| h.join().unwrap()).collect()
+        })
+    }
+
+    /// Process a single task, retrying the formatter on failure.
+    ///
+    /// If every attempt fails, or the processor is shut down, the result holds
+    /// the task `id` and an `error`.
+    fn process_task(&self, task: &Task) -> HashMap<String, String> {
+        let _admitted = {
+            let mut drain = self.drain.lock().unwrap();
+            if drain.shutting_down {
+                return error_result(task, "processor is shut down".to_string());
+            }
+            drain.in_flight += 1;
+            InFlight(self)
+        };
+        self.run_task(task)
+    }
+
+    /// Format a task that has been admitted for processing.
+    fn run_task(&self, task: &Task) -> HashMap<String, String> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!("process_task", task_id = %task.id, outcome = tracing::field::Empty);
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+
+        let started = std::time::Instant::now();
+        let result = self.format_with_retries(task);
+        self.record_duration(started.elapsed());
+        #[cfg(feature = "tracing")]
+        span.record("outcome", if result.is_ok() { "ok" } else { "error" });
+
+        result.unwrap_or_else(|e| error_result(task, e))
+    }
+
+    /// Run the formatter, retrying up to the configured number of times.
+    fn format_with_retries(&self, task: &Task) -> Result<HashMap<String, String>, String> {
+        let mut last_error = String::new();
+        for _ in 0..=self.retries {
+            match (self.formatter)(task) {
+                Ok(map) => return Ok(map),
+                Err(e) => {
+                    log_at!(info, "formatting task {} failed: {}", task.id, e);
+                    last_error = e;
+                }
+            }
+        }
+        log_at!(error, "giving up on task {} after {} attempts", task.id, self.retries + 1);
+        Err(last_error)
+    }
+}
+
+/// Marks one admitted task as finished when dropped, even if its formatter panics.
+struct InFlight<'a>(&'a TaskProcessor);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        let mut drain = self.0.drain.lock().unwrap();
+        drain.in_flight -= 1;
+        drain.finished += 1;
+        self.0.idle.notify_all();
+    }
+}
+
+/// Build the result map for a task that produced no output.
+fn error_result(task: &Task, error: String) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    map.insert("id".to_string(), task.id.clone());
+    map.insert("error".to_string(), error);
+    map
+}
+
+/// Builder that assembles a task processor's configuration in one place.
+pub struct TaskProcessorBuilder {
+    retries: u32,
+    concurrency: usize,
+    formatter: Formatter,
+    on_progress: Option<ProgressCallback>,
+    smoothing: f64,
+}
+
+impl TaskProcessorBuilder {
+    /// Create a builder with no retries, one thread and `format_task`.
+    pub fn new() -> Self {
+        TaskProcessorBuilder {
+            retries: 0,
+            concurrency: 1,
+            formatter: Box::new(|t| Ok(format_task(t))),
+            on_progress: None,
+            smoothing: 0.2,
+        }
+    }
+
+    /// Set how many times a failing formatter is retried.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Set how many threads process tasks.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Set the formatter that turns a task into output.
+    pub fn formatter<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Task) -> Result<HashMap<String, String>, String> + Send + Sync + 'static,
+    {
+        self.formatter = Box::new(f);
+        self
+    }
+
+    /// Set a callback invoked after each processed task.
+    pub fn on_progress<F>(mut self, cb: F) -> Self
+    where
+        F: Fn(usize, usize) + Send + Sync + 'static,
+    {
+        self.on_progress = Some(Box::new(cb));
+        self
+    }
+
+    /// Set the weight of the newest sample in the processing time average, clamped to `0..=1`.
+    pub fn smoothing(mut self, alpha: f64) -> Self {
+        self.smoothing = alpha.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Build the processor for `manager`.
+    pub fn build(self, manager: Arc<TaskManager>) -> TaskProcessor {
+        TaskProcessor {
+            manager,
+            retries: self.retries,
+            concurrency: self.concurrency,
+            formatter: self.formatter,
+            on_progress: self.on_progress,
+            smoothing: self.smoothing,
+            avg_processing: Mutex::new(None),
+            drain: Mutex::new(DrainState::default()),
+            idle: Condvar::new(),
+        }
+    }
+}
+
+impl Default for TaskProcessorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Format a task as a map.
+pub fn format_task(task: &Task) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    map.insert("id".to_string(), task.id.clone());
+    map.insert("name".to_string(), task.name.clone());
+    map.insert("status".to_string(), format!("{:?}", task.status));
+    map.insert("priority".to_string(), task.priority.to_string());
+    map
+}
+
+/// Format a task as a map including metadata, masking sensitive values as `***`.
+///
+/// Metadata keys match `sensitive_keys` case-insensitively and never replace
+/// the base fields from `format_task`.
+pub fn format_task_redacted(task: &Task, sensitive_keys: &[&str]) -> HashMap<String, String> {
+    let mut map = format_task(task);
+    for (key, value) in &task.metadata {
+        let sensitive = sensitive_keys.iter().any(|s| s.eq_ignore_ascii_case(key));
+        let shown = if sensitive { "***".to_string() } else { value.clone() };
+        map.entry(key.clone()).or_insert(shown);
+    }
+    map
+}
+
+/// Format a task as ordered pairs for reproducible output.
+///
+/// Keys come out as id, name, status, priority, then metadata sorted by key.
+pub fn format_task_ordered(task: &Task) -> Vec<(String, String)> {
+    let mut pairs = vec![
+        ("id".to_string(), task.id.clone()),
+        ("name".to_string(), task.name.clone()),
+        ("status".to_string(), format!("{:?}", task.status)),
+        ("priority".to_string(), task.priority.to_string()),
+    ];
+    let mut metadata: Vec<(String, String)> = task.metadata.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    metadata.sort();
+    pairs.extend(metadata);
+    pairs
+}
+
+/// Serialize a task as a JSON object with metadata sorted by key.
+fn task_to_json(task: &Task) -> String {
+    let mut metadata: Vec<(&String, &String)> = task.metadata.iter().collect();
+    metadata.sort();
+    let metadata: Vec<String> = metadata
+        .into_iter()
+        .map(|(k, v)| format!("{}:{}", json_string(k), json_string(v)))
+        .collect();
+    format!(
+        "{{\"id\":{},\"name\":{},\"status\":\"{:?}\",\"priority\":{},\"metadata\":{{{}}}}}",
+        json_string(&task.id),
+        json_string(&task.name),
+        task.status,
+        task.priority,
+        metadata.join(",")
+    )
+}
+
+/// Quote and escape a string as a JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Unused function for deadcode detection.
+#[allow(dead_code)]
+fn unused_function() -> &'static str {
+    "never used"
+}
+
+fn main() {
+    let manager = TaskManager::new();
+    let task = create_task("Test Task", 1);
+    manager.add_task(task).unwrap();
+    println!("Created {} tasks", manager.count());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_task() {
+        let task = create_task("Test", 1);
+        assert!(!task.id.is_empty());
+        assert_eq!(task.name, "Test");
+        assert_eq!(task.priority, 1);
+    }
+
+    #[test]
+    fn test_task_manager() {
+        let manager = TaskManager::new();
+        let task = create_task("Test", 1);
+        manager.add_task(task).unwrap();
+        assert_eq!(manager.count(), 1);
+    }
+
+    #[test]
+    fn test_deep_clone_is_independent() {
+        let manager = TaskManager::new();
+        manager.add_task(Task::new("1".to_string(), "Original".to_string())).unwrap();
+
+        let copy = manager.deep_clone();
+        copy.update_task("1", Some("Changed".to_string()), Some(Status::Active));
+        copy.add_task(Task::new("2".to_string(), "Extra".to_string())).unwrap();
+
+        assert_eq!(manager.count(), 1);
+        let original = manager.get_task("1").unwrap();
+        assert_eq!(original.name, "Original");
+        assert_eq!(original.status, Status::Pending);
+        assert_eq!(copy.count(), 2);
+    }
+
+    #[test]
+    fn test_task_eq_ignores_timestamps() {
+        let a = Task::new("1".to_string(), "Same".to_string()).with_priority(3);
+        let mut b = a.clone();
+        b.created_at = a.created_at + Duration::from_secs(60);
+        assert_eq!(a, b);
+
+        let c = b.clone().with_status(Status::Active);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_task_hash_by_id() {
+        use std::collections::HashSet;
+
+        let first = Task::new("1".to_string(), "First".to_string());
+        let mut duplicate = first.clone();
+        duplicate.created_at = first.created_at + Duration::from_secs(5);
+
+        let mut set = HashSet::new();
+        set.insert(first);
+        set.insert(Task::new("2".to_string(), "Second".to_string()));
+        set.insert(duplicate);
+        assert_eq!(set.len(), 2);
+
+        let mut by_id = HashSet::new();
+        assert!(by_id.insert(ById(Task::new("1".to_string(), "First".to_string()))));
+        assert!(by_id.insert(ById(Task::new("2".to_string(), "Second".to_string()))));
+        assert!(!by_id.insert(ById(Task::new("1".to_string(), "Renamed".to_string()))));
+        assert_eq!(by_id.len(), 2);
+        assert_eq!(by_id.get("1").unwrap().0.name, "First");
+
+        let mut by_status = HashMap::new();
+        *by_status.entry(Status::Pending).or_insert(0) += 1;
+        *by_status.entry(Status::Pending).or_insert(0) += 1;
+        assert_eq!(by_status[&Status::Pending], 2);
+    }
+
+    #[test]
+    fn test_subscribe_receives_events() {
+        let manager = TaskManager::new();
+        let first = manager.subscribe();
+        let second = manager.subscribe();
+
+        let task = Task::new("1".to_string(), "Watched".to_string());
+        manager.add_task(task.clone()).unwrap();
+        assert!(manager.delete_task("1"));
+        assert!(!manager.delete_task("1"));
+
+        for rx in [first, second] {
+            let events: Vec<TaskEvent> = rx.try_iter().collect();
+            assert_eq!(events, vec![TaskEvent::Added(task.clone()), TaskEvent::Deleted("1".to_string())]);
+        }
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let manager = TaskManager::new();
+        manager.add_task(Task::new("1".to_string(), "Keep".to_string())).unwrap();
+        manager.add_task(Task::new("2".to_string(), "Also keep".to_string())).unwrap();
+        let snapshot = manager.snapshot();
+
+        manager.update_task("1", Some("Renamed".to_string()), Some(Status::Failed));
+        manager.delete_task("2");
+        manager.add_task(Task::new("3".to_string(), "Temporary".to_string())).unwrap();
+
+        let events = manager.subscribe();
+        manager.restore(snapshot.clone());
+        assert_eq!(manager.get_tasks::<fn(&Task) -> bool>(None), snapshot.tasks());
+
+        let events: Vec<TaskEvent> = events.try_iter().collect();
+        let deleted: Vec<&str> = events
+            .iter()
+            .filter_map(|e| match e {
+                TaskEvent::Deleted(id) => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(deleted, vec!["1", "3"]);
+        let added: Vec<&Task> = events
+            .iter()
+            .filter_map(|e| match e {
+                TaskEvent::Added(task) => Some(task),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(added, snapshot.tasks().iter().collect::<Vec<_>>());
+        assert_eq!(events.len(), 4);
+    }
+
+    #[test]
+    fn test_update_task_versioned_rejects_stale() {
+        let manager = TaskManager::new();
+        manager.add_task(Task::new("1".to_string(), "Versioned".to_string())).unwrap();
+        let seen = manager.get_task("1").unwrap().version;
+
+        let first = manager.update_task_versioned("1", seen, None, Some(Status::Active)).unwrap();
+        assert_eq!(first.version, seen + 1);
+
+        let second = manager.update_task_versioned("1", seen, None, Some(Status::Failed));
+        assert_eq!(second, Err("version conflict".to_string()));
+        assert_eq!(manager.get_task("1").unwrap().status, Status::Active);
+    }
+
+    #[test]
+    fn test_expire_stale() {
+        let past = SystemTime::now() - Duration::from_secs(60);
+        let future = SystemTime::now() + Duration::from_secs(3600);
+
+        let manager = TaskManager::new();
+        manager.add_task(Task::new("old".to_string(), "Old".to_string()).with_expiry(past)).unwrap();
+        manager.add_task(Task::new("new".to_string(), "New".to_string()).with_expiry(future)).unwrap();
+        manager.add_task(Task::new("none".to_string(), "No expiry".to_string())).unwrap();
+
+        assert_eq!(manager.expire_stale(), vec!["old".to_string()]);
+        assert_eq!(manager.get_task("old").unwrap().status, Status::Failed);
+        assert_eq!(manager.get_task("new").unwrap().status, Status::Pending);
+        assert!(manager.expire_stale().is_empty());
+
+        let deleting = TaskManager::new().with_expiry_policy(ExpiryPolicy::Delete);
+        deleting.add_task(Task::new("old".to_string(), "Old".to_string()).with_expiry(past)).unwrap();
+        deleting.add_task(Task::new("none".to_string(), "No expiry".to_string())).unwrap();
+        assert_eq!(deleting.expire_stale(), vec!["old".to_string()]);
+        assert_eq!(deleting.count(), 1);
+    }
+
+    #[test]
+    fn test_search_index_matches_scan() {
+        let manager = TaskManager::new().with_index();
+        let task = |id: &str, name: &str| Task::new(id.to_string(), name.to_string());
+        manager.add_task(task("1", "Deploy api").with_metadata("env", "prod")).unwrap();
+        manager.add_task(task("2", "Depot audit")).unwrap();
+        manager.add_task(task("3", "Write docs").with_metadata("env", "Production")).unwrap();
+        manager.add_task(task("4", "Prod hotfix")).unwrap();
+        manager.update_task("2", Some("Deploy web".to_string()), None);
+        manager.delete_task("4");
+
+        let scan = |term: &str| -> Vec<String> {
+            let term = term.to_lowercase();
+            let mut ids: Vec<String> = manager
+                .get_tasks::<fn(&Task) -> bool>(None)
+                .into_iter()
+                .filter(|t| {
+                    std::iter::once(&t.name)
+                        .chain(t.metadata.values())
+                        .flat_map(|text| index::tokenize(text).collect::<Vec<_>>())
+                        .any(|word| word.starts_with(&term))
+                })
+                .map(|t| t.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        for term in ["dep", "PROD", "audit", "docs", "hotfix"] {
+            let found: Vec<String> = manager.search_index(term).into_iter().map(|t| t.id).collect();
+            assert_eq!(found, scan(term), "term {}", term);
+        }
+        assert_eq!(manager.search_index("dep").len(), 2);
+        assert!(manager.search_index("audit").is_empty());
+        assert!(TaskManager::new().search_index("dep").is_empty());
+    }
+
+    #[test]
+    fn test_delete_cascade() {
+        let manager = TaskManager::new();
+        let task = |id: &str| Task::new(id.to_string(), format!("Task {}", id));
+        manager.add_task(task("root")).unwrap();
+        manager.add_task(task("a").with_dependency("root")).unwrap();
+        manager.add_task(task("b").with_dependency("a").with_dependency("c")).unwrap();
+        manager.add_task(task("c").with_dependency("b")).unwrap();
+        manager.add_task(task("other")).unwrap();
+
+        assert_eq!(manager.delete_cascade("root"), vec!["root", "a", "b", "c"]);
+        assert_eq!(manager.count(), 1);
+        assert!(manager.get_task("other").is_some());
+        assert!(manager.delete_cascade("missing").is_empty());
+    }
+
+    #[test]
+    fn test_completion_ratio() {
+        let manager = TaskManager::new();
+        assert_eq!(manager.completion_ratio(), 0.0);
+        let statuses = [Status::Completed, Status::Failed, Status::Completed, Status::Pending];
+        for (i, status) in statuses.into_iter().enumerate() {
+            manager.add_task(Task::new(i.to_string(), "Task".to_string()).with_status(status)).unwrap();
+        }
+        assert_eq!(manager.completion_ratio(), 0.5);
+    }
+
+    #[test]
+    fn test_breached_deadlines() {
+        let clock = Arc::new(clock::MockClock::new());
+        let manager = TaskManager::new().with_clock(clock.clone());
+        let now = clock.system_now();
+        let late = Task::new("late".to_string(), "Late".to_string())
+            .with_deadline(now - Duration::from_secs(1));
+        let soon = Task::new("soon".to_string(), "Soon".to_string())
+            .with_deadline(now + Duration::from_secs(60));
+        let done = late.clone().with_status(Status::Completed);
+        manager.add_task(late).unwrap();
+        manager.add_task(soon).unwrap();
+        manager.add_task(Task { id: "done".to_string(), ..done }).unwrap();
+        manager.add_task(Task::new("open".to_string(), "Open".to_string())).unwrap();
+
+        let ids: Vec<String> = manager.breached_deadlines().into_iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec!["late"]);
+        clock.advance(Duration::from_secs(120));
+        assert_eq!(manager.breached_deadlines().len(), 2);
+    }
+
+    #[test]
+    fn test_peek_next() {
+        let manager = TaskManager::new();
+        assert!(manager.peek_next(PickOrder::Fifo).is_none());
+        for (id, priority) in [("1", 2), ("2", 8), ("3", 8)] {
+            manager.add_task(Task::new(id.to_string(), "Task".to_string()).with_priority(priority)).unwrap();
+        }
+        assert_eq!(manager.peek_next(PickOrder::Fifo).unwrap().id, "1");
+        assert_eq!(manager.peek_next(PickOrder::HighestPriority).unwrap().id, "2");
+        assert_eq!(manager.count(), 3);
+        assert_eq!(manager.pop_front().unwrap().id, "1");
+    }
+
+    #[test]
+    fn test_aging_lets_old_low_priority_task_win() {
+        let clock = Arc::new(clock::MockClock::new());
+        let manager = TaskManager::new().with_clock(clock.clone()).with_aging(Duration::from_secs(60));
+        let mut old = Task::new("old".to_string(), "Old".to_string()).with_priority(1);
+        old.created_at = clock.system_now();
+        manager.add_task(old).unwrap();
+
+        let add_fresh = |id: &str| {
+            let mut fresh = Task::new(id.to_string(), "Fresh".to_string()).with_priority(5);
+            fresh.created_at = clock.system_now();
+            manager.add_task(fresh).unwrap();
+        };
+        let ids = |tasks: Vec<Task>| tasks.into_iter().map(|t| t.id).collect::<Vec<_>>();
+
+        clock.advance(Duration::from_secs(120));
+        add_fresh("early");
+        assert_eq!(ids(manager.get_tasks_sorted()), vec!["early", "old"]);
+
+        clock.advance(Duration::from_secs(480));
+        add_fresh("late");
+        assert_eq!(ids(manager.get_tasks_sorted()), vec!["early", "old", "late"]);
+        manager.delete_task("early");
+        assert_eq!(manager.peek_next(PickOrder::HighestPriority).unwrap().id, "old");
+        assert_eq!(manager.get_task("old").unwrap().priority, 1);
+    }
+
+    #[test]
+    fn test_pop_front_is_fifo() {
+        let manager = TaskManager::new().with_bloom_filter(8);
+        for i in 0..4 {
+            manager.add_task(Task::new(i.to_string(), format!("Task {}", i))).unwrap();
+        }
+        assert_eq!(manager.pop_front().unwrap().id, "0");
+        assert_eq!(manager.pop_front().unwrap().id, "1");
+        assert_eq!(manager.pop_back().unwrap().id, "3");
+        assert_eq!(manager.count(), 1);
+        assert!(manager.get_task("0").is_none());
+        assert!(!manager.probably_contains("0"));
+
+        manager.add_task(Task::new("4".to_string(), "Task 4".to_string())).unwrap();
+        let drained: Vec<String> = manager.drain().into_iter().map(|t| t.id).collect();
+        assert_eq!(drained, vec!["2", "4"]);
+        assert_eq!(manager.count(), 0);
+        assert!(manager.pop_front().is_none());
+        assert!(manager.drain().is_empty());
+    }
+
+    #[test]
+    fn test_bloom_filter_no_false_negatives() {
+        let manager = TaskManager::new().with_bloom_filter(1000);
+        for i in 0..1000 {
+            manager.add_task(Task::new(format!("id-{}", i), "Task".to_string())).unwrap();
+        }
+        assert!((0..1000).all(|i| manager.probably_contains(&format!("id-{}", i))));
+        let false_positives = (1000..2000)
+            .filter(|i| manager.probably_contains(&format!("id-{}", i)))
+            .count();
+        assert!(false_positives < 100, "{} false positives", false_positives);
+
+        assert!(manager.delete_task("id-7"));
+        assert!(!manager.probably_contains("id-7"));
+        assert!(manager.probably_contains("id-8"));
+        manager.clear();
+        assert!(!manager.probably_contains("id-8"));
+        assert!(TaskManager::new().probably_contains("anything"));
+    }
+
+    #[test]
+    fn test_add_task_rejects_duplicate_ids() {
+        let manager = TaskManager::new().with_bloom_filter(8);
+        let events = manager.subscribe();
+        manager.add_task(Task::new("1".to_string(), "First".to_string())).unwrap();
+        assert_eq!(
+            manager.add_task(Task::new("1".to_string(), "Again".to_string())),
+            Err("Task ID already exists: 1".to_string())
+        );
+        assert_eq!(manager.count(), 1);
+        assert_eq!(manager.get_task("1").unwrap().name, "First");
+        assert_eq!(events.try_iter().count(), 1);
+
+        assert!(manager.delete_task("1"));
+        assert!(!manager.probably_contains("1"));
+        let csv = "id,name,status,priority\n2,Two,Pending,1\n2,Again,Pending,1\n";
+        assert_eq!(manager.import_csv(csv), Err("Task ID already exists: 2".to_string()));
+        assert_eq!(manager.count(), 0);
+    }
+
+    #[test]
+    fn test_sample_reservoir() {
+        use rng::SeededRng;
+
+        let manager = TaskManager::new();
+        for i in 0..50 {
+            manager.add_task(Task::new(i.to_string(), format!("Task {}", i))).unwrap();
+        }
+        let ids = |tasks: Vec<Task>| tasks.into_iter().map(|t| t.id).collect::<Vec<_>>();
+        let first = ids(manager.sample(5, &mut SeededRng::new(3)));
+        assert_eq!(first, ids(manager.sample(5, &mut SeededRng::new(3))));
+        assert_eq!(first.len(), 5);
+        let distinct: std::collections::HashSet<&String> = first.iter().collect();
+        assert_eq!(distinct.len(), 5);
+
+        assert_eq!(manager.sample(100, &mut SeededRng::new(3)).len(), 50);
+        assert!(manager.sample(0, &mut SeededRng::new(3)).is_empty());
+    }
+
+    #[test]
+    fn test_pick_weighted_is_deterministic() {
+        use rng::SeededRng;
+
+        let manager = TaskManager::new();
+        assert!(manager.pick_weighted(&mut SeededRng::new(1)).is_none());
+
+        manager.add_task(Task::new("low".to_string(), "Low".to_string()).with_priority(-3)).unwrap();
+        manager.add_task(Task::new("high".to_string(), "High".to_string()).with_priority(9)).unwrap();
+        manager
+            .add_task(Task::new("done".to_string(), "Done".to_string()).with_priority(100).with_status(Status::Completed))
+            .unwrap();
+
+        let first = manager.pick_weighted(&mut SeededRng::new(7)).unwrap();
+        let again = manager.pick_weighted(&mut SeededRng::new(7)).unwrap();
+        assert_eq!(first.id, again.id);
+
+        let mut rng = SeededRng::new(7);
+        let picks: Vec<String> = (0..100).map(|_| manager.pick_weighted(&mut rng).unwrap().id).collect();
+        assert!(!picks.contains(&"done".to_string()));
+        assert!(picks.contains(&"low".to_string()));
+        assert!(picks.iter().filter(|id| *id == "high").count() > 50);
+    }
+
+    #[test]
+    fn test_claim_next_and_release() {
+        let manager = TaskManager::new();
+        manager.add_task(Task::new("1".to_string(), "One".to_string())).unwrap();
+        manager.add_task(Task::new("2".to_string(), "Two".to_string())).unwrap();
+
+        let a = manager.claim_next("worker-a", Duration::from_secs(60)).unwrap();
+        let b = manager.claim_next("worker-b", Duration::ZERO).unwrap();
+        assert_ne!(a.id, b.id);
+        assert_eq!(a.status, Status::Claimed);
+        assert_eq!(a.claim.unwrap().worker_id, "worker-a");
+        assert!(manager.claim_next("worker-c", Duration::from_secs(60)).is_none());
+
+        assert_eq!(manager.release_expired_claims(), vec![b.id.clone()]);
+        let again = manager.claim_next("worker-c", Duration::from_secs(60)).unwrap();
+        assert_eq!(again.id, b.id);
+    }
+
+    #[test]
+    fn test_claim_lease_expires_with_mock_clock() {
+        let clock = Arc::new(clock::MockClock::new());
+        let manager = TaskManager::new().with_clock(clock.clone());
+        manager.add_task(Task::new("1".to_string(), "One".to_string())).unwrap();
+        manager.claim_next("worker", Duration::from_secs(60)).unwrap();
+
+        clock.advance(Duration::from_secs(59));
+        assert!(manager.release_expired_claims().is_empty());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(manager.release_expired_claims(), vec!["1"]);
+    }
+
+    #[test]
+    fn test_get_tasks_preserves_insertion_order() {
+        let manager = TaskManager::new();
+        for id in ["c", "a", "b"] {
+            manager.add_task(Task::new(id.to_string(), id.to_uppercase())).unwrap();
+        }
+        assert_eq!(manager.get_task("a").unwrap().name, "A");
+        let ids: Vec<String> = manager.get_tasks::<fn(&Task) -> bool>(None).into_iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec!["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_count_matches_len_under_contention() {
+        use std::thread;
+
+        let manager = Arc::new(TaskManager::new());
+        let handles: Vec<_> = (0..8)
+            .map(|worker| {
+                let manager = Arc::clone(&manager);
+                thread::spawn(move || {
+                    for i in 0..200 {
+                        let id = format!("{}-{}", worker, i);
+                        manager.add_task(Task::new(id.clone(), "Stress".to_string())).unwrap();
+                        if i % 3 == 0 {
+                            manager.delete_task(&id);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let actual = manager.get_tasks::<fn(&Task) -> bool>(None).len();
+        assert_eq!(manager.count(), actual);
+        assert_eq!(actual, 8 * (200 - 67));
+
+        manager.clear();
+        assert_eq!(manager.count(), 0);
+    }
+
+    #[test]
+    fn test_filter_by_status_any() {
+        let tasks = vec![
+            Task::new("1".to_string(), "A".to_string()),
+            Task::new("2".to_string(), "B".to_string()).with_status(Status::Active),
+            Task::new("3".to_string(), "C".to_string()).with_status(Status::Completed),
+        ];
+        let open = filter_by_status_any(&tasks, &[Status::Pending, Status::Active]);
+        let ids: Vec<&str> = open.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "2"]);
+        assert!(filter_by_status_any(&tasks, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_project_tasks() {
+        let manager = TaskManager::new();
+        manager.add_task(Task::new("1".to_string(), "Low".to_string()).with_priority(1)).unwrap();
+        manager.add_task(Task::new("2".to_string(), "High".to_string()).with_priority(8)).unwrap();
+
+        let expected = vec![("Low".to_string(), 1), ("High".to_string(), 8)];
+        let projected: Vec<(String, i32)> = manager.project(|t| (t.name.clone(), t.priority));
+        assert_eq!(projected, expected);
+
+        let tasks = manager.get_tasks::<fn(&Task) -> bool>(None);
+        assert_eq!(project_tasks(&tasks, |t| (t.name.clone(), t.priority)), expected);
+    }
+
+    #[test]
+    fn test_get_tasks_page() {
+        let manager = TaskManager::new();
+        for i in 0..10 {
+            let task = Task::new(i.to_string(), format!("Task {}", i)).with_priority(i);
+            manager.add_task(task).unwrap();
+        }
+
+        let page = manager.get_tasks_page(Some(|t: &Task| t.priority >= 3), 3, 3);
+        assert_eq!(page.total, 7);
+        assert_eq!(page.total_pages, 3);
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].id, "9");
+
+        let all = manager.get_tasks_page::<fn(&Task) -> bool>(None, 1, 4);
+        assert_eq!((all.total, all.total_pages, all.items.len()), (10, 3, 4));
+    }
+
+    #[test]
+    fn test_validate_task_with_policy() {
+        let strict = NamePolicy {
+            max_len: Some(5),
+            allow_control: false,
+        };
+        let long = Task::new("1".to_string(), "Too long".to_string());
+        assert_eq!(
+            validate_task_with_policy(&long, &strict),
+            Err("Task name is 8 characters, max is 5".to_string())
+        );
+
+        let newline = Task::new("2".to_string(), "a\nb".to_string());
+        assert_eq!(
+            validate_task_with_policy(&newline, &strict),
+            Err("Task name contains control character '\\n'".to_string())
+        );
+
+        let default = NamePolicy::default();
+        assert!(validate_task_with_policy(&long, &default).is_ok());
+        assert!(validate_task_with_policy(&newline, &default).is_ok());
+        assert!(validate_task_with_policy(&Task::new("3".to_string(), String::new()), &default).is_err());
+    }
+
+    #[test]
+    fn test_validate_task_all() {
+        let blank = Task::new(String::new(), String::new());
+        assert_eq!(
+            validate_task_all(&blank),
+            Err(vec!["Task ID is required".to_string(), "Task name is required".to_string()])
+        );
+        assert_eq!(validate_task(&blank), Err("Task ID is required".to_string()));
+
+        let schema = TaskSchema {
+            required_metadata: vec!["owner".to_string()],
+        };
+        let policy = NamePolicy {
+            max_len: Some(3),
+            allow_control: false,
+        };
+        let task = Task::new("1".to_string(), "Long\nname".to_string());
+        let errors = validate_task_all_with(&task, Some(&schema), Some(&policy)).unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0], "Metadata 'owner' is required");
+        assert!(validate_task_all(&task).is_ok());
+    }
+
+    #[test]
+    fn test_validate_task_with_schema() {
+        let schema = TaskSchema {
+            required_metadata: vec!["owner".to_string(), "team".to_string()],
+        };
+        let base = Task::new("1".to_string(), "Schema".to_string()).with_metadata("owner", "ana");
+
+        assert_eq!(validate_task_with_schema(&base, &schema), Err("Metadata 'team' is required".to_string()));
+
+        let empty = base.clone().with_metadata("team", "");
+        assert_eq!(validate_task_with_schema(&empty, &schema), Err("Metadata 'team' must not be empty".to_string()));
+
+        let valid = base.with_metadata("team", "core");
+        assert!(validate_task_with_schema(&valid, &schema).is_ok());
+        assert!(validate_task(&Task::new("2".to_string(), "Plain".to_string())).is_ok());
+    }
+
+    #[test]
+    fn test_transition_all() {
+        let manager = TaskManager::new();
+        for (id, status) in [("1", Status::Active), ("2", Status::Pending), ("3", Status::Active)] {
+            manager.add_task(Task::new(id.to_string(), format!("Task {}", id)).with_status(status)).unwrap();
+        }
+        let events = manager.subscribe();
+
+        assert_eq!(manager.transition_all(Status::Active, Status::Completed), 2);
+        assert_eq!(manager.get_task("1").unwrap().status, Status::Completed);
+        assert_eq!(manager.get_task("2").unwrap().status, Status::Pending);
+        assert_eq!(manager.get_task("3").unwrap().version, 1);
+        assert_eq!(events.try_iter().count(), 2);
+        assert_eq!(manager.transition_all(Status::Active, Status::Completed), 0);
+    }
+
+    #[test]
+    fn test_task_template() {
+        let template = TaskTemplate::new()
+            .priority(4)
+            .status(Status::Active)
+            .metadata("team", "core");
+        let manager = TaskManager::new();
+        let a = manager.add_from_template(&template, "Deploy api").unwrap();
+        let b = manager.add_from_template(&template, "Deploy web").unwrap();
+        assert_ne!(a.id, b.id);
+        assert_eq!(a.metadata, b.metadata);
+        assert_eq!(b.metadata["team"], "core");
+        assert_eq!((b.priority, b.status), (4, Status::Active));
+        assert_eq!(manager.get_task(&b.id).unwrap().name, "Deploy web");
+    }
+
+    #[test]
+    fn test_add_with_defaults() {
+        let manager = TaskManager::new().with_default_priority(5);
+        let task = manager.add_with_defaults("Triage").unwrap();
+        assert_eq!(task.priority, 5);
+        assert_eq!(manager.get_task(&task.id).unwrap().priority, 5);
+        assert!(manager.add_with_defaults("").is_err());
+        assert_eq!(TaskManager::new().add_with_defaults("Plain").unwrap().priority, 1);
+    }
+
+    #[test]
+    fn test_status_u8_round_trip() {
+        let all = [Status::Pending, Status::Claimed, Status::Active, Status::Completed, Status::Failed];
+        for (code, status) in all.iter().enumerate() {
+            assert_eq!(status.as_u8(), code as u8);
+            assert_eq!(Status::from_u8(status.as_u8()).as_ref(), Some(status));
+        }
+        assert_eq!(Status::from_u8(5), None);
+        assert_eq!(Status::from_u8(u8::MAX), None);
+    }
+
+    #[test]
+    fn test_interned_metadata_keys() {
+        let manager = TaskManager::new().with_interning();
+        for i in 0..50 {
+            let task = Task::new(i.to_string(), format!("Task {}", i))
+                .with_metadata("owner", &format!("user-{}", i))
+                .with_metadata("team", "core");
+            manager.add_task(task).unwrap();
+        }
+        assert_eq!(manager.interned_key_count(), 2);
+
+        let last = manager.get_task("49").unwrap();
+        assert_eq!(last.metadata["owner"], "user-49");
+        assert_eq!(last.metadata["team"], "core");
+        manager.update_task("49", None, Some(Status::Active)).unwrap();
+        assert_eq!(manager.get_task("49").unwrap().metadata, last.metadata);
+        assert_eq!(manager.pop_back().unwrap().metadata, last.metadata);
+        assert_eq!(TaskManager::new().interned_key_count(), 0);
+    }
+
+    #[test]
+    fn test_deep_clone_owns_its_interner() {
+        let task = |id: &str, key: &str| Task::new(id.to_string(), id.to_string()).with_metadata(key, "v");
+        let manager = TaskManager::new().with_interning();
+        manager.add_task(task("1", "a")).unwrap();
+        let copy = manager.deep_clone();
+        copy.add_task(task("2", "b")).unwrap();
+        manager.add_task(task("3", "c")).unwrap();
+        manager.add_task(task("4", "d")).unwrap();
+        assert_eq!(copy.interned_key_count(), 2);
+        assert_eq!(manager.interned_key_count(), 3);
+    }
+
+    #[test]
+    fn test_metadata_case_insensitive() {
+        let mut task = Task::new("1".to_string(), "Meta".to_string())
+            .with_metadata("Owner", "upper")
+            .with_metadata("Team", "core");
+        assert_eq!(task.metadata_get_ci("owner"), Some(&"upper".to_string()));
+        assert_eq!(task.metadata_get_ci("TEAM"), Some(&"core".to_string()));
+        assert_eq!(task.metadata_get_ci("missing"), None);
+
+        task.metadata.insert("owner".to_string(), "lower".to_string());
+        task.normalize_metadata_keys();
+        assert_eq!(task.metadata.len(), 2);
+        assert_eq!(task.metadata["owner"], "lower");
+        assert_eq!(task.metadata["team"], "core");
+    }
+
+    #[test]
+    fn test_task_diff() {
+        let old = Task::new("1".to_string(), "Diff".to_string())
+            .with_metadata("owner", "ana")
+            .with_metadata("team", "core");
+        assert!(old.diff(&old.clone()).is_empty());
+
+        let new = old.clone().with_status(Status::Active).with_metadata("owner", "bo");
+        let diff = old.diff(&new);
+        assert_eq!(diff.fields, vec!["status"]);
+        assert_eq!(diff.metadata_changed, vec!["owner".to_string()]);
+        assert!(diff.metadata_added.is_empty());
+        assert!(diff.metadata_removed.is_empty());
+    }
+
+    #[test]
+    fn test_task_merge() {
+        let local = Task::new("1".to_string(), "Merge".to_string())
+            .with_priority(7)
+            .with_status(Status::Completed)
+            .with_metadata("owner", "ana")
+            .with_metadata("team", "core");
+        let remote = Task::new("1".to_string(), "Merge".to_string())
+            .with_priority(2)
+            .with_status(Status::Active)
+            .with_metadata("owner", "bo")
+            .with_metadata("region", "eu");
+
+        let merged = local.merge(&remote).unwrap();
+        assert_eq!(merged.priority, 7);
+        assert_eq!(merged.status, Status::Completed);
+        assert_eq!(merged.metadata.len(), 3);
+        assert_eq!(merged.metadata["owner"], "bo");
+        assert_eq!(merged.metadata["team"], "core");
+
+        let advanced = remote.merge(&local.clone().with_status(Status::Claimed)).unwrap();
+        assert_eq!(advanced.status, Status::Active);
+
+        let other = Task::new("2".to_string(), "Other".to_string());
+        assert!(local.merge(&other).is_err());
+    }
+
+    #[test]
+    fn test_task_to_builder_round_trip() {
+        let task = TaskBuilder::new("1", "Built")
+            .priority(4)
+            .status(Status::Active)
+            .metadata("owner", "ana")
+            .build();
+        let same = task.clone().to_builder().build();
+        assert_eq!(same, task);
+        assert_eq!(same.created_at, task.created_at);
+
+        let bumped = task.clone().to_builder().priority(9).build();
+        assert_eq!(bumped.priority, 9);
+        assert_eq!(task.diff(&bumped).fields, vec!["priority"]);
+    }
+
+    /// Logger that records every message; installed once for all logging tests.
+    #[cfg(feature = "logging")]
+    struct Capture(Mutex<Vec<(log::Level, String)>>);
+
+    #[cfg(feature = "logging")]
+    impl log::Log for Capture {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+        fn log(&self, record: &log::Record) {
+            self.0.lock().unwrap().push((record.level(), record.args().to_string()));
+        }
+        fn flush(&self) {}
+    }
+
+    #[cfg(feature = "logging")]
+    fn captured_logs() -> &'static Capture {
+        static CAPTURE: Capture = Capture(Mutex::new(Vec::new()));
+        static INSTALL: std::sync::Once = std::sync::Once::new();
+        INSTALL.call_once(|| {
+            log::set_logger(&CAPTURE).unwrap();
+            log::set_max_level(log::LevelFilter::Trace);
+        });
+        &CAPTURE
+    }
+
+    #[cfg(feature = "logging")]
+    fn logged(level: log::Level, needle: &str) -> bool {
+        let records = captured_logs().0.lock().unwrap();
+        records.iter().any(|(l, msg)| *l == level && msg.contains(needle))
+    }
+
+    #[cfg(feature = "logging")]
+    #[test]
+    fn test_validation_failure_logs_warning() {
+        captured_logs();
+        let manager = TaskManager::new();
+        assert!(manager.add_task(Task::new("bad".to_string(), String::new())).is_err());
+        assert!(logged(log::Level::Warn, "rejected task \"bad\": Task name is required"));
+    }
+
+    #[cfg(feature = "logging")]
+    #[test]
+    fn test_slow_lock_hold_logs_warning() {
+        captured_logs();
+        let manager = TaskManager::new().with_slow_lock_threshold(Duration::from_millis(5));
+        let guard = manager.lock_tasks();
+        std::thread::sleep(Duration::from_millis(20));
+        drop(guard);
+        assert!(logged(log::Level::Warn, "task lock held for"));
+    }
+
+    #[test]
+    fn test_format_task_redacted() {
+        let task = Task::new("1".to_string(), "Deploy".to_string())
+            .with_metadata("api_key", "sk-123")
+            .with_metadata("Password", "hunter2")
+            .with_metadata("team", "core");
+        let map = format_task_redacted(&task, &["api_key", "password"]);
+        assert_eq!(map["api_key"], "***");
+        assert_eq!(map["Password"], "***");
+        assert_eq!(map["team"], "core");
+        assert_eq!(map["name"], "Deploy");
+        assert_eq!(map["id"], "1");
+        assert!(!map.values().any(|v| v.contains("sk-123") || v.contains("hunter2")));
+    }
+
+    #[test]
+    fn test_format_task_ordered() {
+        let task = Task::new("7".to_string(), "Ordered".to_string())
+            .with_priority(2)
+            .with_metadata("zone", "b")
+            .with_metadata("owner", "ana");
+        let keys: Vec<String> = format_task_ordered(&task).into_iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, vec!["id", "name", "status", "priority", "owner", "zone"]);
+        assert_eq!(format_task_ordered(&task)[2].1, "Pending");
+    }
+
+    #[test]
+    fn test_avg_processing_time_converges() {
+        let manager = Arc::new(TaskManager::new());
+        for i in 0..8 {
+            manager.add_task(Task::new(i.to_string(), format!("Task {}", i))).unwrap();
+        }
+        let delay = Duration::from_millis(10);
+        let processor = TaskProcessor::builder()
+            .smoothing(0.5)
+            .formatter(move |t| {
+                std::thread::sleep(delay);
+                Ok(format_task(t))
+            })
+            .build(manager);
+        assert!(processor.avg_processing_time().is_none());
+
+        processor.process_all();
+        let avg = processor.avg_processing_time().unwrap();
+        assert!(avg >= delay && avg < delay * 3, "average {:?}", avg);
+
+        processor.reset_stats();
+        assert!(processor.avg_processing_time().is_none());
+    }
+
+    #[test]
+    fn test_shutdown_reports_abandoned_tasks() {
+        let manager = Arc::new(TaskManager::new());
+        for id in ["fast", "slow-1", "slow-2"] {
+            manager.add_task(Task::new(id.to_string(), id.to_string())).unwrap();
+        }
+        let started = Arc::new(AtomicUsize::new(0));
+        let processor = {
+            let started = Arc::clone(&started);
+            Arc::new(
+                TaskProcessor::builder()
+                    .concurrency(3)
+                    .formatter(move |t| {
+                        started.fetch_add(1, Ordering::SeqCst);
+                        let delay = if t.id == "fast" { 100 } else { 1_500 };
+                        std::thread::sleep(Duration::from_millis(delay));
+                        Ok(format_task(t))
+                    })
+                    .build(manager),
+            )
+        };
+        let worker = {
+            let processor = Arc::clone(&processor);
+            std::thread::spawn(move || processor.process_all())
+        };
+        while started.load(Ordering::SeqCst) < 3 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let report = processor.shutdown(Duration::from_millis(500));
+        assert_eq!(report, ShutdownReport { completed: 1, abandoned: 2 });
+        assert!(processor.is_shut_down());
+        let skipped = processor.process_all();
+        assert!(skipped.iter().all(|r| r["error"] == "processor is shut down"));
+        assert_eq!(started.load(Ordering::SeqCst), 3);
+
+        let results = worker.join().unwrap();
+        assert!(results.iter().all(|r| !r.contains_key("error")));
+        assert_eq!(processor.shutdown(Duration::ZERO), ShutdownReport { completed: 0, abandoned: 0 });
+    }
+
+    #[test]
+    fn test_shutdown_after_formatter_panic() {
+        let manager = Arc::new(TaskManager::new());
+        manager.add_task(Task::new("1".to_string(), "Boom".to_string())).unwrap();
+        let processor = Arc::new(
+            TaskProcessor::builder()
+                .formatter(|_| panic!("formatter failed"))
+                .build(manager),
+        );
+        let worker = {
+            let processor = Arc::clone(&processor);
+            std::thread::spawn(move || processor.process_all())
+        };
+        assert!(worker.join().is_err());
+
+        let started = std::time::Instant::now();
+        let report = processor.shutdown(Duration::from_secs(5));
+        assert_eq!(report, ShutdownReport { completed: 0, abandoned: 0 });
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_in_flight_tasks() {
+        let manager = Arc::new(TaskManager::new());
+        for i in 0..3 {
+            manager.add_task(Task::new(i.to_string(), format!("Task {}", i))).unwrap();
+        }
+        let started = Arc::new(AtomicUsize::new(0));
+        let release = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let processor = {
+            let (started, release) = (Arc::clone(&started), Arc::clone(&release));
+            Arc::new(
+                TaskProcessor::builder()
+                    .concurrency(3)
+                    .formatter(move |t| {
+                        started.fetch_add(1, Ordering::SeqCst);
+                        while !release.load(Ordering::SeqCst) {
+                            std::thread::sleep(Duration::from_millis(1));
+                        }
+                        Ok(format_task(t))
+                    })
+                    .build(manager),
+            )
+        };
+        let worker = tokio::task::spawn_blocking({
+            let processor = Arc::clone(&processor);
+            move || processor.process_all()
+        });
+        while started.load(Ordering::SeqCst) < 3 {
+            tokio::task::yield_now().await;
+        }
+
+        let shutdown = tokio::task::spawn_blocking({
+            let processor = Arc::clone(&processor);
+            move || processor.shutdown(Duration::from_secs(5))
+        });
+        while !processor.is_shut_down() {
+            tokio::task::yield_now().await;
+        }
+        release.store(true, Ordering::SeqCst);
+        let report = shutdown.await.unwrap();
+        assert_eq!(report, ShutdownReport { completed: 3, abandoned: 0 });
+        let results = worker.await.unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|r| !r.contains_key("error")));
+        assert!(processor.process_all().iter().all(|r| r["error"] == "processor is shut down"));
+    }
+
+    #[test]
+    fn test_task_processor_builder() {
+        let manager = Arc::new(TaskManager::new());
+        for i in 0..6 {
+            manager.add_task(Task::new(i.to_string(), format!("Task {}", i))).unwrap();
+        }
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let processor = {
+            let attempts = Arc::clone(&attempts);
+            let progress = Arc::clone(&progress);
+            TaskProcessor::builder()
+                .retries(2)
+                .concurrency(3)
+                .formatter(move |t| {
+                    if t.id == "3" && attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                        return Err("flaky".to_string());
+                    }
+                    if t.id == "5" {
+                        return Err("broken".to_string());
+                    }
+                    let mut map = HashMap::new();
+                    map.insert("label".to_string(), t.name.to_uppercase());
+                    Ok(map)
+                })
+                .on_progress(move |done, total| progress.lock().unwrap().push((done, total)))
+                .build(Arc::clone(&manager))
+        };
+
+        let results = processor.process_all();
+        assert_eq!(results.len(), 6);
+        assert_eq!(results[0]["label"], "TASK 0");
+        assert_eq!(results[3]["label"], "TASK 3");
+        assert_eq!(results[5]["error"], "broken");
+
+        let mut seen = progress.lock().unwrap().clone();
+        seen.sort();
+        assert_eq!(seen, (1..=6).map(|done| (done, 6)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_metadata_key_frequency() {
+        let manager = TaskManager::new();
+        manager
+            .add_task(Task::new("1".to_string(), "A".to_string()).with_metadata("team", "x").with_metadata("env", "prod"))
+            .unwrap();
+        manager.add_task(Task::new("2".to_string(), "B".to_string()).with_metadata("team", "y")).unwrap();
+        manager.add_task(Task::new("3".to_string(), "C".to_string())).unwrap();
+
+        let counts = manager.metadata_key_frequency();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["team"], 2);
+        assert_eq!(counts["env"], 1);
+    }
+
+    #[test]
+    fn test_query_str() {
+        let manager = TaskManager::new();
+        manager.add_task(Task::new("1".to_string(), "Low".to_string()).with_status(Status::Active)).unwrap();
+        manager
+            .add_task(Task::new("2".to_string(), "High".to_string()).with_status(Status::Active).with_priority(7))
+            .unwrap();
+        manager.add_task(Task::new("3".to_string(), "Queued".to_string()).with_priority(9)).unwrap();
+
+        let ids = |expr: &str| -> Vec<String> {
+            manager.query_str(expr).unwrap().into_iter().map(|t| t.id).collect()
+        };
+        assert_eq!(ids("status=active AND priority>=5"), vec!["2"]);
+        assert_eq!(ids("status=pending OR name=\"Low\""), vec!["1", "3"]);
+
+        assert_eq!(
+            manager.query_str("status=active AND priority>>5"),
+            Err("Expected a value at position 27".to_string())
+        );
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let source = TaskManager::new();
+        source.add_task(Task::new("1".to_string(), "Plain".to_string()).with_priority(3)).unwrap();
+        source
+            .add_task(Task::new("2".to_string(), "Ship it, \"now\"".to_string()).with_status(Status::Active))
+            .unwrap();
+        let csv = source.export_csv();
+        assert!(csv.starts_with("id,name,status,priority\n"));
+
+        let target = TaskManager::new();
+        assert_eq!(target.import_csv(&csv), Ok(2));
+        assert_eq!(target.snapshot().tasks(), source.snapshot().tasks());
+
+        let bad = "id,name,status,priority\n3,Fine,Pending,1\n4,,Pending,1\n";
+        assert_eq!(target.import_csv(bad), Err("Line 3: Task name is required".to_string()));
+        assert_eq!(target.count(), 2);
+    }
+
+    /// Manager with a plain task and one whose name needs quoting.
+    fn export_fixture() -> TaskManager {
+        let manager = TaskManager::new();
+        manager.add_task(Task::new("1".to_string(), "Plain".to_string()).with_priority(3)).unwrap();
+        manager
+            .add_task(Task::new("2".to_string(), "Say \"hi\", then\tgo".to_string()).with_metadata("k", "v"))
+            .unwrap();
+        manager
+    }
+
+    #[cfg(any(feature = "json", feature = "yaml", feature = "msgpack"))]
+    #[test]
+    fn test_export_import_round_trip_each_format() {
+        let formats = [
+            #[cfg(feature = "json")]
+            Format::Json,
+            #[cfg(feature = "yaml")]
+            Format::Yaml,
+            #[cfg(feature = "msgpack")]
+            Format::MsgPack,
+        ];
+        let source = export_fixture();
+        let all = |m: &TaskManager| m.get_tasks::<fn(&Task) -> bool>(None);
+        for format in formats {
+            let bytes = source.export(format).unwrap();
+            let restored = TaskManager::new();
+            assert_eq!(restored.import(format, &bytes), Ok(2), "{:?}", format);
+            assert_eq!(all(&restored), all(&source), "{:?}", format);
+            assert!(restored.import(format, b"\xff\x00garbage").is_err(), "{:?}", format);
+            assert_eq!(restored.count(), 2);
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_import_json_verified_rejects_corruption() {
+        let export = export_fixture().export_json_with_checksum();
+        assert!(export.starts_with("crc32:"));
+        let restored = TaskManager::new();
+        assert_eq!(restored.import_json_verified(&export), Ok(2));
+
+        let mut corrupted = export.into_bytes();
+        let last = corrupted.len() - 3;
+        corrupted[last] ^= 0x01;
+        let corrupted = String::from_utf8(corrupted).unwrap();
+        let target = TaskManager::new();
+        assert_eq!(target.import_json_verified(&corrupted), Err("checksum mismatch".to_string()));
+        assert_eq!(target.count(), 0);
+        assert!(target.import_json_verified("[]").is_err());
+    }
+
+    #[test]
+    fn test_recover_from_wal() {
+        let path = std::env::temp_dir().join(format!("task-wal-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let manager = TaskManager::new().with_wal(&path).unwrap();
+            for i in 0..3 {
+                let task = Task::new(i.to_string(), format!("Task\t{}", i)).with_metadata("k", "v");
+                manager.add_task(task).unwrap();
+            }
+            manager.update_task("1", Some("Renamed".to_string()), Some(Status::Active));
+            assert!(manager.delete_task("2"));
+            assert!(!manager.delete_task("missing"));
+        }
+        let intact = std::fs::read(&path).unwrap().len() as u64;
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"00000000\tadd\t9\tpartial")
+            .unwrap();
+
+        let recovered = TaskManager::recover_from_wal(&path).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), intact);
+        assert_eq!(recovered.count(), 2);
+        let renamed = recovered.get_task("1").unwrap();
+        assert_eq!(renamed.name, "Renamed");
+        assert_eq!((renamed.status, renamed.version), (Status::Active, 1));
+        assert_eq!(recovered.get_task("0").unwrap().metadata["k"], "v");
+        assert_eq!(recovered.get_task("0").unwrap().name, "Task\t0");
+        assert!(recovered.get_task("2").is_none());
+
+        recovered.add_task(Task::new("3".to_string(), "After".to_string())).unwrap();
+        drop(recovered);
+        assert_eq!(TaskManager::recover_from_wal(&path).unwrap().count(), 3);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_recover_from_wal_replays_bulk_mutations() {
+        let path = std::env::temp_dir().join(format!("task-wal-bulk-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let clock = Arc::new(clock::MockClock::new());
+        let expected = {
+            let manager = TaskManager::new().with_clock(clock.clone()).with_wal(&path).unwrap();
+            let source = TaskManager::new();
+            for i in 0..6 {
+                source.add_task(Task::new(i.to_string(), format!("Task {}", i))).unwrap();
+            }
+            manager.import_csv(&source.export_csv()).unwrap();
+            let snapshot = manager.snapshot();
+            manager.clear();
+            manager.restore(snapshot);
+
+            let child = Task::new("c".to_string(), "Child".to_string()).with_dependency("p");
+            manager.add_task(Task::new("p".to_string(), "Parent".to_string())).unwrap();
+            manager.add_task(child).unwrap();
+            assert_eq!(manager.delete_cascade("p").len(), 2);
+            assert_eq!(manager.pop_front().unwrap().id, "0");
+            assert_eq!(manager.pop_back().unwrap().id, "5");
+
+            let claimed = manager.claim_next("w1", Duration::from_secs(30)).unwrap();
+            assert_eq!(claimed.id, "1");
+            assert_eq!(manager.transition_all(Status::Pending, Status::Active), 3);
+            manager.snapshot().tasks
+        };
+
+        let recovered = TaskManager::recover_from_wal(&path).unwrap();
+        assert_eq!(recovered.snapshot().tasks, expected);
+        let claimed = recovered.get_task("1").unwrap();
+        assert_eq!(claimed.status, Status::Claimed);
+        assert_eq!(claimed.claim.unwrap().worker_id, "w1");
+        assert_eq!(recovered.get_task("2").unwrap().status, Status::Active);
+        drop(recovered);
+
+        clock.advance(Duration::from_secs(60));
+        let manager = TaskManager::recover_from_wal(&path).unwrap().with_clock(clock.clone());
+        assert_eq!(manager.release_expired_claims(), vec!["1"]);
+        assert_eq!(manager.drain().len(), 4);
+        manager.add_task(Task::new("n".to_string(), "New".to_string())).unwrap();
+        drop(manager);
+        let tasks = TaskManager::recover_from_wal(&path).unwrap().snapshot().tasks;
+        assert_eq!(tasks, vec![Task::new("n".to_string(), "New".to_string())]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_export_csv_to_streams() {
+        let source = export_fixture();
+        let mut buffer = Vec::new();
+        source.export_csv_to(&mut buffer).unwrap();
+        assert_eq!(String::from_utf8(buffer.clone()).unwrap(), source.export_csv());
+
+        let target = TaskManager::new();
+        target.import_csv(std::str::from_utf8(&buffer).unwrap()).unwrap();
+        let names: Vec<String> = target.snapshot().tasks().iter().map(|t| t.name.clone()).collect();
+        assert_eq!(names, vec!["Plain", "Say \"hi\", then\tgo"]);
+    }
+
+    #[test]
+    fn test_export_streams_across_chunks() {
+        let source = TaskManager::new();
+        for i in 0..STREAM_CHUNK * 2 + 1 {
+            source.add_task(Task::new(i.to_string(), format!("Task {}", i))).unwrap();
+        }
+        let target = TaskManager::new();
+        assert_eq!(target.import_csv(&source.export_csv()), Ok(STREAM_CHUNK * 2 + 1));
+        assert_eq!(target.snapshot().tasks(), source.snapshot().tasks());
+    }
+
+    #[test]
+    fn test_stream_skips_only_deleted_tasks() {
+        let manager = TaskManager::new();
+        for i in 0..STREAM_CHUNK * 2 {
+            manager.add_task(Task::new(i.to_string(), format!("Task {}", i))).unwrap();
+        }
+        let mut stream = manager.stream_tasks();
+        let mut streamed = vec![stream.next().unwrap().id];
+        // Shift every later task left, and remove one that has not been copied yet.
+        assert!(manager.delete_task("1"));
+        let unread = (STREAM_CHUNK + 10).to_string();
+        assert!(manager.delete_task(&unread));
+        streamed.extend(stream.map(|t| t.id));
+
+        assert!(!streamed.contains(&unread));
+        for task in manager.snapshot().tasks() {
+            assert!(streamed.contains(&task.id), "task {} missing from stream", task.id);
+        }
+        assert_eq!(streamed.len(), STREAM_CHUNK * 2 - 1);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_export_json_to_streams() {
+        let source = export_fixture();
+        let mut buffer = Vec::new();
+        source.export_json_to(&mut buffer).unwrap();
+        assert_eq!(String::from_utf8(buffer.clone()).unwrap(), source.export_json());
+
+        let parsed: serde_json::Value = std::str::from_utf8(&buffer).unwrap().parse().unwrap();
+        let items = parsed.as_array().unwrap();
+        assert_eq!(items.len(), 2);
+        for (item, task) in items.iter().zip(source.snapshot().tasks()) {
+            let field = |key: &str| item.get(key).and_then(|v| v.as_str()).map(str::to_string);
+            assert_eq!(field("id"), Some(task.id.clone()));
+            assert_eq!(field("name"), Some(task.name.clone()));
+            assert_eq!(field("status"), Some(format!("{:?}", task.status)));
+            assert_eq!(item.get("priority").and_then(|v| v.as_i64()), Some(task.priority as i64));
+        }
+        let metadata = items[1].get("metadata").unwrap();
+        assert_eq!(metadata.get("k").and_then(|v| v.as_str()), Some("v"));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_json_gz_round_trip() {
+        let source = export_fixture();
+        let compressed = source.export_json_gz().unwrap();
+        assert_eq!(&compressed[..2], &[0x1f, 0x8b]);
+
+        let target = TaskManager::new();
+        assert_eq!(target.import_json_gz(&compressed), Ok(2));
+        assert_eq!(target.snapshot().tasks(), source.snapshot().tasks());
+        assert!(target.import_json_gz(b"not gzip").is_err());
+        assert_eq!(target.count(), 2);
+    }
+
+    #[cfg(feature = "fuzzy")]
+    #[test]
+    fn test_fuzzy_search() {
+        let manager = TaskManager::new();
+        manager.add_task(Task::new("1".to_string(), "rollback".to_string())).unwrap();
+        manager.add_task(Task::new("2".to_string(), "Deploys".to_string())).unwrap();
+        manager.add_task(Task::new("3".to_string(), "deploy".to_string())).unwrap();
+
+        let found: Vec<String> = manager.fuzzy_search("deplo;", 2).into_iter().map(|t| t.id).collect();
+        assert_eq!(found, vec!["3", "2"]);
+
+        let exact: Vec<String> = manager.fuzzy_search("deplo;", 1).into_iter().map(|t| t.id).collect();
+        assert_eq!(exact, vec!["3"]);
     }
 }
@@ -0,0 +1,284 @@
+//! Parser for task filter expressions such as `status=active AND priority>=5`.
+//!
+//! Grammar (keywords are case-insensitive):
+//!
+//! ```text
+//! expr       := and_expr ("OR" and_expr)*
+//! and_expr   := primary ("AND" primary)*
+//! primary    := "(" expr ")" | comparison
+//! comparison := field op value
+//! field      := "status" | "priority" | "name"
+//! op         := "=" | "!=" | "<" | "<=" | ">" | ">="
+//! value      := word | "quoted string"
+//! ```
+//!
+//! `status` only supports `=` and `!=` and matches case-insensitively. Errors
+//! report the 0-based character position of the offending token.
+
+use crate::{Status, Task};
+use std::cmp::Ordering;
+
+/// Parsed filter expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Status(CmpOp, Status),
+    Priority(CmpOp, i32),
+    Name(CmpOp, String),
+}
+
+/// Comparison operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            CmpOp::Eq => ordering == Ordering::Equal,
+            CmpOp::Ne => ordering != Ordering::Equal,
+            CmpOp::Lt => ordering == Ordering::Less,
+            CmpOp::Le => ordering != Ordering::Greater,
+            CmpOp::Gt => ordering == Ordering::Greater,
+            CmpOp::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+impl Query {
+    /// Parse an expression.
+    pub fn parse(expr: &str) -> Result<Query, String> {
+        let tokens = tokenize(expr)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            end: expr.chars().count(),
+        };
+        let query = parser.or_expr()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(query),
+            Some((at, _)) => Err(format!("Unexpected token at position {}", at)),
+        }
+    }
+
+    /// Check whether a task satisfies the expression.
+    pub fn matches(&self, task: &Task) -> bool {
+        match self {
+            Query::And(a, b) => a.matches(task) && b.matches(task),
+            Query::Or(a, b) => a.matches(task) || b.matches(task),
+            Query::Status(op, status) => (task.status == *status) == (*op == CmpOp::Eq),
+            Query::Priority(op, priority) => op.holds(task.priority.cmp(priority)),
+            Query::Name(op, name) => op.holds(task.name.as_str().cmp(name.as_str())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Op(CmpOp),
+    And,
+    Or,
+    LParen,
+    RParen,
+}
+
+/// Split an expression into tokens tagged with their character position.
+fn tokenize(expr: &str) -> Result<Vec<(usize, Token)>, String> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let c = chars[i];
+        let token = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => {
+                i += 1;
+                Token::LParen
+            }
+            ')' => {
+                i += 1;
+                Token::RParen
+            }
+            '=' | '!' | '<' | '>' => {
+                let eq_next = chars.get(i + 1) == Some(&'=');
+                i += if eq_next { 2 } else { 1 };
+                Token::Op(match (c, eq_next) {
+                    ('=', _) => CmpOp::Eq,
+                    ('!', true) => CmpOp::Ne,
+                    ('<', false) => CmpOp::Lt,
+                    ('<', true) => CmpOp::Le,
+                    ('>', false) => CmpOp::Gt,
+                    ('>', true) => CmpOp::Ge,
+                    _ => return Err(format!("Expected '!=' at position {}", start)),
+                })
+            }
+            '"' => {
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(format!("Unterminated string starting at position {}", start)),
+                        Some('"') => break,
+                        Some('\\') if chars.get(i + 1).is_some() => {
+                            value.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some(&c) => {
+                            value.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                Token::Str(value)
+            }
+            c if is_word_char(c) => {
+                while i < chars.len() && is_word_char(chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                match word.to_ascii_uppercase().as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    _ => Token::Word(word),
+                }
+            }
+            c => return Err(format!("Unexpected character '{}' at position {}", c, start)),
+        };
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// Position reported for errors at the end of input.
+    end: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<(usize, Token)> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.tokens.get(self.pos).is_some_and(|(_, t)| t == token);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or_expr(&mut self) -> Result<Query, String> {
+        let mut query = self.and_expr()?;
+        while self.eat(&Token::Or) {
+            query = Query::Or(Box::new(query), Box::new(self.and_expr()?));
+        }
+        Ok(query)
+    }
+
+    fn and_expr(&mut self) -> Result<Query, String> {
+        let mut query = self.primary()?;
+        while self.eat(&Token::And) {
+            query = Query::And(Box::new(query), Box::new(self.primary()?));
+        }
+        Ok(query)
+    }
+
+    fn primary(&mut self) -> Result<Query, String> {
+        match self.next() {
+            Some((_, Token::LParen)) => {
+                let query = self.or_expr()?;
+                match self.next() {
+                    Some((_, Token::RParen)) => Ok(query),
+                    Some((at, _)) => Err(format!("Expected ')' at position {}", at)),
+                    None => Err(format!("Expected ')' at position {}", self.end)),
+                }
+            }
+            Some((at, Token::Word(field))) => self.comparison(at, &field),
+            Some((at, _)) => Err(format!("Expected a field at position {}", at)),
+            None => Err(format!("Expected a field at position {}", self.end)),
+        }
+    }
+
+    fn comparison(&mut self, field_at: usize, field: &str) -> Result<Query, String> {
+        let (op_at, op) = match self.next() {
+            Some((at, Token::Op(op))) => (at, op),
+            Some((at, _)) => return Err(format!("Expected an operator at position {}", at)),
+            None => return Err(format!("Expected an operator at position {}", self.end)),
+        };
+        let (value_at, value) = match self.next() {
+            Some((at, Token::Word(v))) | Some((at, Token::Str(v))) => (at, v),
+            Some((at, _)) => return Err(format!("Expected a value at position {}", at)),
+            None => return Err(format!("Expected a value at position {}", self.end)),
+        };
+        match field.to_ascii_lowercase().as_str() {
+            "status" => {
+                if !matches!(op, CmpOp::Eq | CmpOp::Ne) {
+                    return Err(format!("Status only supports '=' and '!=' at position {}", op_at));
+                }
+                let status = parse_status(&value)
+                    .ok_or_else(|| format!("Unknown status '{}' at position {}", value, value_at))?;
+                Ok(Query::Status(op, status))
+            }
+            "priority" => {
+                let priority = value
+                    .parse()
+                    .map_err(|_| format!("Invalid priority '{}' at position {}", value, value_at))?;
+                Ok(Query::Priority(op, priority))
+            }
+            "name" => Ok(Query::Name(op, value)),
+            _ => Err(format!("Unknown field '{}' at position {}", field, field_at)),
+        }
+    }
+}
+
+/// Parse a status name case-insensitively.
+fn parse_status(value: &str) -> Option<Status> {
+    [Status::Pending, Status::Claimed, Status::Active, Status::Completed, Status::Failed]
+        .into_iter()
+        .find(|s| format!("{:?}", s).eq_ignore_ascii_case(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_precedence_and_errors() {
+        let query = Query::parse("name = \"a b\" OR status=active AND priority>=5").unwrap();
+        assert_eq!(
+            query,
+            Query::Or(
+                Box::new(Query::Name(CmpOp::Eq, "a b".to_string())),
+                Box::new(Query::And(
+                    Box::new(Query::Status(CmpOp::Eq, Status::Active)),
+                    Box::new(Query::Priority(CmpOp::Ge, 5)),
+                )),
+            )
+        );
+        assert_eq!(Query::parse("priority >"), Err("Expected a value at position 10".to_string()));
+        assert_eq!(Query::parse("owner=me"), Err("Unknown field 'owner' at position 0".to_string()));
+        assert_eq!(Query::parse("(status=active"), Err("Expected ')' at position 14".to_string()));
+    }
+}
@@ -0,0 +1,219 @@
+//! Retrying fallible operations with backoff.
+
+use crate::rng::{Rng, SeededRng};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// How long to wait between retries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[allow(dead_code)]
+pub enum BackoffStrategy {
+    /// Always wait the base delay.
+    Fixed,
+    /// Double the delay after each attempt.
+    Exponential,
+    /// Wait a random delay up to the exponential one ("full jitter").
+    #[default]
+    ExponentialJitter,
+}
+
+#[allow(dead_code)]
+impl BackoffStrategy {
+    /// Get the delay before retry number `attempt` (0-based).
+    pub fn delay(&self, base: Duration, attempt: u32, rng: &mut dyn Rng) -> Duration {
+        let exponential = base.saturating_mul(1 << attempt.min(16));
+        match self {
+            BackoffStrategy::Fixed => base,
+            BackoffStrategy::Exponential => exponential,
+            BackoffStrategy::ExponentialJitter => {
+                let max = exponential.as_millis() as u64;
+                Duration::from_millis(rng.gen_below(max + 1))
+            }
+        }
+    }
+}
+
+/// How many times to try an operation and how long to wait in between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub struct RetryPolicy {
+    /// Total attempts including the first; zero is treated as one.
+    pub max_attempts: u32,
+    pub backoff: BackoffStrategy,
+    pub base_delay: Duration,
+}
+
+#[allow(dead_code)]
+impl RetryPolicy {
+    /// Create a policy with the default backoff and a 100ms base delay.
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy {
+            max_attempts,
+            backoff: BackoffStrategy::default(),
+            base_delay: Duration::from_millis(100),
+        }
+    }
+
+    /// Set the backoff strategy and its base delay.
+    pub fn with_backoff(mut self, backoff: BackoffStrategy, base_delay: Duration) -> Self {
+        self.backoff = backoff;
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// Get the delay before retry number `attempt` (0-based).
+    pub fn delay(&self, attempt: u32, rng: &mut dyn Rng) -> Duration {
+        self.backoff.delay(self.base_delay, attempt, rng)
+    }
+}
+
+/// Run `op` until it succeeds or the policy's attempts run out, returning the last error.
+///
+/// Blocks the current thread between attempts.
+#[allow(dead_code)]
+pub fn retry<T, E>(policy: RetryPolicy, mut op: impl FnMut() -> Result<T, E>) -> Result<T, E> {
+    let mut rng = SeededRng::from_time();
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(_) if attempt + 1 < policy.max_attempts => {
+                std::thread::sleep(policy.delay(attempt, &mut rng));
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Async `retry`: waits between attempts without blocking the executor.
+#[allow(dead_code)]
+pub async fn retry_async<T, E, F, Fut>(policy: RetryPolicy, op: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut rng = SeededRng::from_time();
+    retry_async_with_delays(policy.max_attempts, |attempt| policy.delay(attempt, &mut rng), op).await
+}
+
+/// Async retry loop taking the delay before each retry from `delay`.
+#[allow(dead_code)]
+pub(crate) async fn retry_async_with_delays<T, E, F, Fut>(
+    max_attempts: u32,
+    mut delay: impl FnMut(u32) -> Duration,
+    mut op: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Err(_) if attempt + 1 < max_attempts => {
+                sleep(delay(attempt)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Wait for `duration` without blocking the executor.
+#[allow(dead_code)]
+pub async fn sleep(duration: Duration) {
+    if !duration.is_zero() {
+        Sleep {
+            deadline: Instant::now() + duration,
+            waker: None,
+        }
+        .await
+    }
+}
+
+/// Future completing at a deadline; a helper thread wakes the task when it passes.
+#[allow(dead_code)]
+struct Sleep {
+    deadline: Instant,
+    waker: Option<Arc<Mutex<Waker>>>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let now = Instant::now();
+        if now >= self.deadline {
+            return Poll::Ready(());
+        }
+        match &self.waker {
+            Some(waker) => waker.lock().unwrap().clone_from(cx.waker()),
+            None => {
+                let waker = Arc::new(Mutex::new(cx.waker().clone()));
+                let remaining = self.deadline - now;
+                let timer = waker.clone();
+                std::thread::spawn(move || {
+                    std::thread::sleep(remaining);
+                    timer.lock().unwrap().wake_by_ref();
+                });
+                self.waker = Some(waker);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        let mut fut = std::pin::pin!(fut);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+                return out;
+            }
+        }
+    }
+
+    #[test]
+    fn test_retry_succeeds_on_third_try() {
+        let policy = RetryPolicy::new(5).with_backoff(BackoffStrategy::Fixed, Duration::ZERO);
+        let mut calls = 0;
+        let result: Result<u32, String> = retry(policy, || {
+            calls += 1;
+            if calls < 3 { Err(format!("attempt {}", calls)) } else { Ok(calls) }
+        });
+        assert_eq!(result, Ok(3));
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn test_retry_exhausts_attempts() {
+        let policy = RetryPolicy::new(3).with_backoff(BackoffStrategy::Fixed, Duration::ZERO);
+        let mut calls = 0;
+        let result: Result<(), String> = retry(policy, || {
+            calls += 1;
+            Err(format!("attempt {}", calls))
+        });
+        assert_eq!(result, Err("attempt 3".to_string()));
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn test_retry_async_waits_between_attempts() {
+        let policy = RetryPolicy::new(3).with_backoff(BackoffStrategy::Fixed, Duration::from_millis(5));
+        let calls = std::cell::Cell::new(0);
+        let started = Instant::now();
+        let result: Result<u32, &str> = block_on(retry_async(policy, || async {
+            calls.set(calls.get() + 1);
+            if calls.get() < 3 { Err("not yet") } else { Ok(calls.get()) }
+        }));
+        assert_eq!(result, Ok(3));
+        assert!(started.elapsed() >= Duration::from_millis(10));
+    }
+}
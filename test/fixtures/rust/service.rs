@@ -1,16 +1,42 @@
 //! Service module for data operations.
 
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use crate::clock::{Clock, SystemClock};
+use crate::retry::retry_async_with_delays;
+pub use crate::retry::BackoffStrategy;
+use crate::rng::{Rng, SeededRng};
+use crate::{Entity, Validate};
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant, SystemTime};
+
+/// User-agent sent by default: the crate name and version.
+#[allow(dead_code)]
+pub const DEFAULT_USER_AGENT: &str =
+    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
 
 /// Configuration for services.
+///
+/// Every request carries `user_agent` and `default_headers`; headers set for a
+/// single call override both.
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 pub struct Config {
     pub api_url: String,
     pub timeout: Duration,
     pub retries: u32,
+    pub retry_delay: Duration,
+    pub backoff: BackoffStrategy,
+    pub health_path: String,
     pub debug: bool,
+    pub user_agent: String,
+    pub default_headers: HashMap<String, String>,
 }
 
 impl Default for Config {
@@ -19,12 +45,47 @@ impl Default for Config {
             api_url: "https://api.example.com".to_string(),
             timeout: Duration::from_secs(5),
             retries: 3,
+            retry_delay: Duration::from_millis(100),
+            backoff: BackoffStrategy::default(),
+            health_path: "/health".to_string(),
             debug: false,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            default_headers: HashMap::new(),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Config {
+    /// Build a config from `API_URL`, `API_TIMEOUT_SECS`, `API_RETRIES` and `API_DEBUG`.
+    ///
+    /// Unset or unparsable variables keep their default value.
+    pub fn from_env() -> Self {
+        let defaults = Config::default();
+        let var = |name: &str| std::env::var(name).ok();
+        Config {
+            api_url: var("API_URL").unwrap_or(defaults.api_url),
+            timeout: var("API_TIMEOUT_SECS")
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.timeout),
+            retries: var("API_RETRIES")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.retries),
+            retry_delay: defaults.retry_delay,
+            backoff: defaults.backoff,
+            health_path: defaults.health_path,
+            debug: var("API_DEBUG")
+                .map(|v| matches!(v.as_str(), "1" | "true"))
+                .unwrap_or(defaults.debug),
+            user_agent: defaults.user_agent,
+            default_headers: defaults.default_headers,
         }
     }
 }
 
 /// Repository trait for data access.
+#[allow(dead_code)]
 pub trait Repository<T> {
     fn save(&self, entity: T) -> Result<(), String>;
     fn find(&self, id: &str) -> Option<T>;
@@ -32,18 +93,64 @@ pub trait Repository<T> {
     fn delete(&self, id: &str) -> bool;
 }
 
+/// Kind of mutation recorded in a change log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum ChangeKind {
+    Saved,
+    Deleted,
+}
+
+/// One entry in a `DataService` change log.
+#[derive(Debug, Clone, PartialEq)]
+#[allow(dead_code)]
+pub struct ChangeRecord {
+    pub kind: ChangeKind,
+    pub id: String,
+    pub at: SystemTime,
+}
+
 /// Generic data service.
+#[allow(dead_code)]
 pub struct DataService<T: Clone> {
     config: Config,
     storage: Arc<Mutex<HashMap<String, T>>>,
+    change_log: Option<Arc<Mutex<Vec<ChangeRecord>>>>,
 }
 
+#[allow(dead_code)]
 impl<T: Clone> DataService<T> {
     /// Create a new data service.
     pub fn new(config: Config) -> Self {
         DataService {
             config,
             storage: Arc::new(Mutex::new(HashMap::new())),
+            change_log: None,
+        }
+    }
+
+    /// Record every save and delete in an append-only change log.
+    pub fn with_change_log(mut self) -> Self {
+        self.change_log = Some(Arc::new(Mutex::new(Vec::new())));
+        self
+    }
+
+    /// Get the recorded changes in order; empty unless `with_change_log` was used.
+    pub fn change_log(&self) -> Vec<ChangeRecord> {
+        match &self.change_log {
+            Some(log) => log.lock().unwrap().clone(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Append to the change log, if enabled; called with the storage lock held.
+    fn record(&self, kind: ChangeKind, id: &str) {
+        if let Some(log) = &self.change_log {
+            log.lock().unwrap().push(ChangeRecord {
+                kind,
+                id: id.to_string(),
+                at: SystemTime::now(),
+            });
         }
     }
 
@@ -60,151 +167,2468 @@ impl<T: Clone> DataService<T> {
     /// Clear all stored entities.
     pub fn clear(&self) {
         let mut storage = self.storage.lock().unwrap();
+        for id in storage.keys() {
+            self.record(ChangeKind::Deleted, id);
+        }
         storage.clear();
     }
+
+    /// Get the stored values matching `pred`; only matches are cloned.
+    pub fn find_all_filtered(&self, pred: impl Fn(&T) -> bool) -> Vec<T> {
+        let storage = self.storage.lock().unwrap();
+        storage.values().filter(|v| pred(v)).cloned().collect()
+    }
+}
+
+#[allow(dead_code)]
+impl<T: Clone + Entity + Validate> DataService<T> {
+    /// Validate then store a batch under one lock; if any entity fails, nothing is stored.
+    pub fn save_all(&self, entities: Vec<T>) -> Result<(), String> {
+        for entity in &entities {
+            entity
+                .validate()
+                .map_err(|e| format!("Entity '{}': {}", entity.get_id(), e))?;
+        }
+        let mut storage = self.storage.lock().unwrap();
+        for entity in entities {
+            self.record(ChangeKind::Saved, entity.get_id());
+            storage.insert(entity.get_id().to_string(), entity);
+        }
+        Ok(())
+    }
+}
+
+impl<T: Clone + Entity> Repository<T> for DataService<T> {
+    fn save(&self, entity: T) -> Result<(), String> {
+        let id = entity.get_id().to_string();
+        let mut storage = self.storage.lock().unwrap();
+        self.record(ChangeKind::Saved, &id);
+        storage.insert(id, entity);
+        Ok(())
+    }
+
+    fn find(&self, id: &str) -> Option<T> {
+        self.storage.lock().unwrap().get(id).cloned()
+    }
+
+    fn find_all(&self) -> Vec<T> {
+        self.storage.lock().unwrap().values().cloned().collect()
+    }
+
+    fn delete(&self, id: &str) -> bool {
+        let mut storage = self.storage.lock().unwrap();
+        let existed = storage.remove(id).is_some();
+        if existed {
+            self.record(ChangeKind::Deleted, id);
+        }
+        existed
+    }
+}
+
+/// Data service for async callers, backed by a `tokio` read-write lock.
+///
+/// Waiting for the lock yields to the executor instead of blocking the thread,
+/// and reads run concurrently.
+#[cfg(feature = "async")]
+#[allow(dead_code)]
+pub struct DataServiceAsync<T: Clone> {
+    config: Config,
+    storage: Arc<tokio::sync::RwLock<HashMap<String, T>>>,
+}
+
+#[cfg(feature = "async")]
+#[allow(dead_code)]
+impl<T: Clone + Entity> DataServiceAsync<T> {
+    /// Create a new async data service.
+    pub fn new(config: Config) -> Self {
+        DataServiceAsync {
+            config,
+            storage: Arc::new(tokio::sync::RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Create with default config.
+    pub fn with_defaults() -> Self {
+        Self::new(Config::default())
+    }
+
+    /// Get the config.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Store an entity under its ID, replacing any previous one.
+    pub async fn save(&self, entity: T) -> Result<(), String> {
+        let id = entity.get_id().to_string();
+        self.storage.write().await.insert(id, entity);
+        Ok(())
+    }
+
+    /// Find an entity by ID.
+    pub async fn find(&self, id: &str) -> Option<T> {
+        self.storage.read().await.get(id).cloned()
+    }
+
+    /// Get all stored entities.
+    pub async fn find_all(&self) -> Vec<T> {
+        self.storage.read().await.values().cloned().collect()
+    }
+
+    /// Delete an entity by ID, returning whether it existed.
+    pub async fn delete(&self, id: &str) -> bool {
+        self.storage.write().await.remove(id).is_some()
+    }
+}
+
+/// Counts events in fixed time buckets so recent rates can be queried.
+///
+/// Counts are kept per `bucket` (one minute by default) and buckets older than
+/// `retention` (one hour by default) age out, so `requests_in_last` is only
+/// as precise as the bucket size.
+#[allow(dead_code)]
+pub struct WindowedMetrics {
+    bucket: Duration,
+    retention: Duration,
+    origin: Instant,
+    clock: Arc<dyn Clock>,
+    buckets: Mutex<VecDeque<(Instant, usize)>>,
+}
+
+#[allow(dead_code)]
+impl WindowedMetrics {
+    /// Create with one-minute buckets kept for an hour.
+    pub fn new() -> Self {
+        Self::with_buckets(Duration::from_secs(60), Duration::from_secs(3600))
+    }
+
+    /// Create with the given bucket size and retention.
+    pub fn with_buckets(bucket: Duration, retention: Duration) -> Self {
+        WindowedMetrics {
+            bucket: bucket.max(Duration::from_millis(1)),
+            retention,
+            origin: Instant::now(),
+            clock: Arc::new(SystemClock),
+            buckets: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Read the current time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.origin = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    /// Count one event now.
+    pub fn record(&self) {
+        let now = self.clock.now();
+        let elapsed = now.saturating_duration_since(self.origin).as_nanos();
+        let start = self.origin + Duration::from_nanos((elapsed - elapsed % self.bucket.as_nanos()) as u64);
+
+        let mut buckets = self.buckets.lock().unwrap();
+        match buckets.back_mut() {
+            Some((last, count)) if *last == start => *count += 1,
+            _ => buckets.push_back((start, 1)),
+        }
+        self.evict(&mut buckets, now);
+    }
+
+    /// Count events in buckets that overlap the last `window`.
+    pub fn requests_in_last(&self, window: Duration) -> usize {
+        let now = self.clock.now();
+        let mut buckets = self.buckets.lock().unwrap();
+        self.evict(&mut buckets, now);
+        let cutoff = now.checked_sub(window);
+        buckets
+            .iter()
+            .filter(|(start, _)| cutoff.is_none_or(|cutoff| *start + self.bucket > cutoff))
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// Drop buckets that ended before the retention period.
+    fn evict(&self, buckets: &mut VecDeque<(Instant, usize)>, now: Instant) {
+        if let Some(cutoff) = now.checked_sub(self.retention) {
+            while buckets.front().is_some_and(|(start, _)| *start + self.bucket <= cutoff) {
+                buckets.pop_front();
+            }
+        }
+    }
+}
+
+impl Default for WindowedMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Append one metric to a Prometheus text exposition.
+#[allow(dead_code)]
+fn push_metric(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    samples: &[(String, u64)],
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{}{} {}", name, labels, value);
+    }
+}
+
+/// Snapshot of cache counters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub size: usize,
+}
+
+/// Outcome of `CacheService::get_result`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum CacheResult<T> {
+    /// A fresh value was found.
+    Hit(T),
+    /// The key was recorded as missing upstream by `set_negative`.
+    NegativeHit,
+    /// Nothing fresh is known about the key.
+    Miss,
+}
+
+#[allow(dead_code)]
+impl<T> CacheResult<T> {
+    /// Get the cached value, treating negative hits as misses.
+    pub fn into_option(self) -> Option<T> {
+        match self {
+            CacheResult::Hit(value) => Some(value),
+            CacheResult::NegativeHit | CacheResult::Miss => None,
+        }
+    }
 }
 
 /// Cache entry with timestamp.
+#[allow(dead_code)]
 struct CacheEntry<T> {
     value: T,
     timestamp: Instant,
 }
 
+#[allow(dead_code)]
+impl<T: Clone> CacheEntry<T> {
+    /// Get how long ago the entry was stored.
+    fn age(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.timestamp)
+    }
+
+    /// Read the value and its age, restarting a fresh entry's TTL if `touch` is set.
+    fn read(&mut self, now: Instant, ttl: Duration, touch: bool) -> (T, Duration) {
+        let age = self.age(now);
+        if touch && age < ttl {
+            self.timestamp = now;
+        }
+        (self.value.clone(), age)
+    }
+}
+
+/// Value deferred by `CacheService::set_lazy` until the first `get`.
+#[allow(dead_code)]
+struct LazyEntry<T> {
+    init: Mutex<Option<Box<dyn FnOnce() -> T + Send>>>,
+    value: OnceLock<T>,
+}
+
+#[allow(dead_code)]
+impl<T: Clone> LazyEntry<T> {
+    /// Compute the value on first call; concurrent callers wait for that one computation.
+    ///
+    /// Returns `None` once the thunk has panicked, which leaves `init` poisoned or empty.
+    fn force(&self) -> Option<T> {
+        if let Some(value) = self.value.get() {
+            return Some(value.clone());
+        }
+        let mut init = self.init.lock().ok()?;
+        if let Some(value) = self.value.get() {
+            return Some(value.clone());
+        }
+        let value = init.take()?();
+        Some(self.value.get_or_init(|| value).clone())
+    }
+}
+
+/// Pending writes staged by `CacheService::set_buffered`, oldest first.
+#[allow(dead_code)]
+type WriteBuffer<K, T> = Vec<(K, CacheEntry<T>)>;
+
+/// Number of buffered writes that triggers an automatic flush.
+#[allow(dead_code)]
+const DEFAULT_BUFFER_THRESHOLD: usize = 64;
+
 /// Caching service with TTL.
-pub struct CacheService<T: Clone> {
+///
+/// Keys default to `String`; pass a structured key type as `K` to keep
+/// unrelated namespaces from colliding. Lookups accept any borrowed form of
+/// the key, so `CacheService<T>` still takes `&str`.
+///
+/// Writes made with `set_buffered` are staged in a write buffer and moved into
+/// the cache in one batch. Locks are always taken buffer first, then cache,
+/// then the map of values deferred by `set_lazy`, then negative entries.
+///
+/// Negative entries from `set_negative` carry their own TTL, and storing a
+/// value for a key drops its negative entry.
+///
+/// With a sliding TTL, every `get` hit restarts the entry's TTL; `peek` and
+/// `get_with_age` never count as accesses.
+#[allow(dead_code)]
+pub struct CacheService<T: Clone, K = String> {
     ttl: Duration,
-    cache: Arc<Mutex<HashMap<String, CacheEntry<T>>>>,
+    sliding: bool,
+    cache: Arc<Mutex<HashMap<K, CacheEntry<T>>>>,
+    buffer: Arc<Mutex<WriteBuffer<K, T>>>,
+    lazy: Arc<Mutex<HashMap<K, Arc<LazyEntry<T>>>>>,
+    negatives: Arc<Mutex<HashMap<K, Instant>>>,
+    buffer_threshold: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    clock: Arc<dyn Clock>,
 }
 
-impl<T: Clone> CacheService<T> {
+#[allow(dead_code)]
+impl<T: Clone, K: Eq + Hash + Clone> CacheService<T, K> {
     /// Create a new cache service.
     pub fn new(ttl: Duration) -> Self {
         CacheService {
             ttl,
+            sliding: false,
             cache: Arc::new(Mutex::new(HashMap::new())),
+            buffer: Arc::new(Mutex::new(Vec::new())),
+            lazy: Arc::new(Mutex::new(HashMap::new())),
+            negatives: Arc::new(Mutex::new(HashMap::new())),
+            buffer_threshold: DEFAULT_BUFFER_THRESHOLD,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            clock: Arc::new(SystemClock),
         }
     }
 
-    /// Get a value from cache.
-    pub fn get(&self, key: &str) -> Option<T> {
-        let cache = self.cache.lock().unwrap();
-        if let Some(entry) = cache.get(key) {
-            if entry.timestamp.elapsed() < self.ttl {
-                return Some(entry.value.clone());
+    /// Read the current time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Set how many buffered writes trigger an automatic flush.
+    pub fn with_buffer_threshold(mut self, threshold: usize) -> Self {
+        self.buffer_threshold = threshold.max(1);
+        self
+    }
+
+    /// Restart an entry's TTL whenever `get` reads it.
+    pub fn with_sliding_ttl(mut self, sliding: bool) -> Self {
+        self.sliding = sliding;
+        self
+    }
+
+    /// Get the configured time-to-live.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Get a value from cache, including buffered writes and values deferred by `set_lazy`.
+    ///
+    /// Negative entries read as `None`; use `get_result` to tell them apart from misses.
+    pub fn get<Q>(&self, key: &Q) -> Option<T>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.get_result(key).into_option()
+    }
+
+    /// Get a value like `get`, distinguishing a fresh negative entry from a miss.
+    ///
+    /// Negative hits count as hits in `stats`.
+    pub fn get_result<Q>(&self, key: &Q) -> CacheResult<T>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let result = if self.negative_fresh(key) {
+            CacheResult::NegativeHit
+        } else {
+            self.force_lazy(key)
+                .or_else(|| {
+                    self.lookup(key, self.sliding)
+                        .filter(|(_, age)| *age < self.ttl)
+                        .map(|(value, _)| value)
+                })
+                .map_or(CacheResult::Miss, CacheResult::Hit)
+        };
+        let counter = match result {
+            CacheResult::Miss => &self.misses,
+            CacheResult::Hit(_) | CacheResult::NegativeHit => &self.hits,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        result
+    }
+
+    /// Check for an unexpired negative entry, dropping it once expired.
+    fn negative_fresh<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let mut negatives = self.negatives.lock().unwrap();
+        match negatives.get(key) {
+            Some(expires) if *expires > self.clock.now() => true,
+            Some(_) => {
+                negatives.remove(key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Record that `key` is missing upstream for `ttl`, replacing any stored value.
+    pub fn set_negative(&self, key: K, ttl: Duration) {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.retain(|(k, _)| *k != key);
+        let mut cache = self.cache.lock().unwrap();
+        cache.remove(&key);
+        self.lazy.lock().unwrap().remove(&key);
+        let expires = self.clock.now() + ttl;
+        self.negatives.lock().unwrap().insert(key, expires);
+    }
+
+    /// Get a fresh value without counting it as an access.
+    pub fn peek<Q>(&self, key: &Q) -> Option<T>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.lookup(key, false)
+            .filter(|(_, age)| *age < self.ttl)
+            .map(|(value, _)| value)
+    }
+
+    /// Get a value and how long ago it was stored, even if it is past the TTL.
+    ///
+    /// Supports stale-while-revalidate: compare the age against `ttl()` to
+    /// decide whether to refresh. Entries already removed by
+    /// `cleanup_expired` are gone for good.
+    pub fn get_with_age<Q>(&self, key: &Q) -> Option<(T, Duration)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.lookup(key, false)
+    }
+
+    /// Compute a deferred value, storing it as a normal entry whose TTL starts now.
+    ///
+    /// Runs outside the cache locks so a slow computation only blocks callers of
+    /// the same key. Only the caller that retires the deferred entry stores the
+    /// result, so a `set` that lands meanwhile is not overwritten. An entry whose
+    /// thunk panicked is evicted and reads as a miss.
+    fn force_lazy<Q>(&self, key: &Q) -> Option<T>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let pending = self.lazy.lock().unwrap().get(key).cloned()?;
+        let value = pending.force();
+        let _buffer = self.buffer.lock().unwrap();
+        let mut cache = self.cache.lock().unwrap();
+        let mut lazy = self.lazy.lock().unwrap();
+        if let Some((stored_key, current)) = lazy.get_key_value(key) {
+            if Arc::ptr_eq(current, &pending) {
+                let stored_key = stored_key.clone();
+                lazy.remove(key);
+                if let Some(value) = &value {
+                    cache.insert(stored_key, CacheEntry {
+                        value: value.clone(),
+                        timestamp: self.clock.now(),
+                    });
+                }
             }
         }
-        None
+        value
     }
 
-    /// Set a value in cache.
-    pub fn set(&self, key: String, value: T) {
+    /// Store `f` to compute the value on the first `get`; `peek` and `get_with_age` don't run it.
+    ///
+    /// Replaces any existing value for the key. The thunk runs once even when
+    /// several threads get the key concurrently, and the TTL starts once it has run.
+    pub fn set_lazy(&self, key: K, f: impl FnOnce() -> T + Send + 'static)
+    where
+        T: 'static,
+    {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.retain(|(k, _)| *k != key);
         let mut cache = self.cache.lock().unwrap();
+        cache.remove(&key);
+        let entry = LazyEntry {
+            init: Mutex::new(Some(Box::new(f))),
+            value: OnceLock::new(),
+        };
+        self.lazy.lock().unwrap().insert(key.clone(), Arc::new(entry));
+        self.negatives.lock().unwrap().remove(&key);
+    }
+
+    /// Find the newest entry for a key, checking the write buffer first.
+    fn lookup<Q>(&self, key: &Q, touch: bool) -> Option<(T, Duration)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let now = self.clock.now();
+        let mut buffer = self.buffer.lock().unwrap();
+        if let Some((_, entry)) = buffer.iter_mut().rev().find(|(k, _)| k.borrow() == key) {
+            return Some(entry.read(now, self.ttl, touch));
+        }
+        let mut cache = self.cache.lock().unwrap();
+        cache.get_mut(key).map(|entry| entry.read(now, self.ttl, touch))
+    }
+
+    /// Set a value in cache, superseding any buffered write for the key.
+    pub fn set(&self, key: K, value: T) {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.retain(|(k, _)| *k != key);
+        let mut cache = self.cache.lock().unwrap();
+        self.lazy.lock().unwrap().remove(&key);
+        self.negatives.lock().unwrap().remove(&key);
         cache.insert(key, CacheEntry {
             value,
-            timestamp: Instant::now(),
+            timestamp: self.clock.now(),
         });
     }
 
+    /// Stage a value in the write buffer, flushing once the threshold is hit.
+    pub fn set_buffered(&self, key: K, value: T) {
+        let mut buffer = self.buffer.lock().unwrap();
+        self.lazy.lock().unwrap().remove(&key);
+        self.negatives.lock().unwrap().remove(&key);
+        buffer.push((key, CacheEntry {
+            value,
+            timestamp: self.clock.now(),
+        }));
+        if buffer.len() >= self.buffer_threshold {
+            self.flush_locked(&mut buffer);
+        }
+    }
+
+    /// Move all buffered writes into the cache.
+    pub fn flush(&self) {
+        let mut buffer = self.buffer.lock().unwrap();
+        self.flush_locked(&mut buffer);
+    }
+
+    /// Drain an already-locked buffer into the cache under a single lock.
+    fn flush_locked(&self, buffer: &mut WriteBuffer<K, T>) {
+        if buffer.is_empty() {
+            return;
+        }
+        let mut cache = self.cache.lock().unwrap();
+        cache.extend(buffer.drain(..));
+    }
+
     /// Delete a value from cache.
-    pub fn delete(&self, key: &str) -> bool {
+    pub fn delete<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let mut buffer = self.buffer.lock().unwrap();
+        let buffered = buffer.len();
+        buffer.retain(|(k, _)| k.borrow() != key);
+        let mut cache = self.cache.lock().unwrap();
+        let lazy = self.lazy.lock().unwrap().remove(key).is_some();
+        let negative = self.negatives.lock().unwrap().remove(key).is_some();
+        cache.remove(key).is_some() || buffer.len() < buffered || lazy || negative
+    }
+
+    /// Dump all fresh entries, e.g. to warm another cache on startup.
+    pub fn export(&self) -> Vec<(K, T)> {
+        let now = self.clock.now();
+        let buffer = self.buffer.lock().unwrap();
+        let cache = self.cache.lock().unwrap();
+        let mut fresh: HashMap<K, T> = cache
+            .iter()
+            .filter(|(_, entry)| entry.age(now) < self.ttl)
+            .map(|(key, entry)| (key.clone(), entry.value.clone()))
+            .collect();
+        for (key, entry) in buffer.iter() {
+            if entry.age(now) < self.ttl {
+                fresh.insert(key.clone(), entry.value.clone());
+            } else {
+                fresh.remove(key);
+            }
+        }
+        fresh.into_iter().collect()
+    }
+
+    /// Insert exported entries, each with a fresh timestamp.
+    pub fn import(&self, entries: Vec<(K, T)>) {
+        let mut buffer = self.buffer.lock().unwrap();
+        self.flush_locked(&mut buffer);
         let mut cache = self.cache.lock().unwrap();
-        cache.remove(key).is_some()
+        let now = self.clock.now();
+        for (key, value) in entries {
+            cache.insert(key, CacheEntry { value, timestamp: now });
+        }
     }
 
     /// Clear all values from cache.
     pub fn clear(&self) {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.clear();
         let mut cache = self.cache.lock().unwrap();
         cache.clear();
+        self.lazy.lock().unwrap().clear();
+        self.negatives.lock().unwrap().clear();
+    }
+
+    /// Get hit/miss counts from `get` and the number of stored keys.
+    pub fn stats(&self) -> CacheStats {
+        let buffer = self.buffer.lock().unwrap();
+        let cache = self.cache.lock().unwrap();
+        let buffered_only = buffer
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| !cache.contains_key(*key))
+            .collect::<HashSet<_>>()
+            .len();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            size: cache.len() + buffered_only,
+        }
+    }
+
+    /// Render cache stats in Prometheus text exposition format.
+    pub fn metrics_prometheus(&self) -> String {
+        let stats = self.stats();
+        let mut out = String::new();
+        push_metric(
+            &mut out,
+            "cache_hits_total",
+            "counter",
+            "Cache lookups that found a fresh value.",
+            &[(String::new(), stats.hits)],
+        );
+        push_metric(
+            &mut out,
+            "cache_misses_total",
+            "counter",
+            "Cache lookups that found nothing fresh.",
+            &[(String::new(), stats.misses)],
+        );
+        push_metric(
+            &mut out,
+            "cache_entries",
+            "gauge",
+            "Keys currently stored in the cache.",
+            &[(String::new(), stats.size as u64)],
+        );
+        out
     }
 
     /// Remove expired entries.
     pub fn cleanup_expired(&self) -> usize {
+        let now = self.clock.now();
         let mut cache = self.cache.lock().unwrap();
-        let expired: Vec<String> = cache
+        let expired: Vec<K> = cache
             .iter()
-            .filter(|(_, entry)| entry.timestamp.elapsed() >= self.ttl)
+            .filter(|(_, entry)| entry.age(now) >= self.ttl)
             .map(|(key, _)| key.clone())
             .collect();
         let count = expired.len();
         for key in expired {
             cache.remove(&key);
         }
+        drop(cache);
+        self.negatives.lock().unwrap().retain(|_, expires| *expires > now);
+        if count > 0 {
+            log_at!(debug, "evicted {} expired cache entries", count);
+        }
         count
     }
 }
 
-/// HTTP client for API requests.
-pub struct ApiClient {
-    config: Config,
+#[allow(dead_code)]
+impl<T: Clone, K: Eq + Hash + Clone + AsRef<str>> CacheService<T, K> {
+    /// Remove every entry whose key starts with `prefix`, returning how many keys were removed.
+    pub fn invalidate_prefix(&self, prefix: &str) -> usize {
+        let mut buffer = self.buffer.lock().unwrap();
+        let mut cache = self.cache.lock().unwrap();
+        let mut removed = HashSet::new();
+        let mut keep = |key: &K| {
+            let matches = key.as_ref().starts_with(prefix);
+            if matches {
+                removed.insert(key.clone());
+            }
+            !matches
+        };
+        buffer.retain(|(key, _)| keep(key));
+        cache.retain(|key, _| keep(key));
+        self.lazy.lock().unwrap().retain(|key, _| keep(key));
+        self.negatives.lock().unwrap().retain(|key, _| keep(key));
+        removed.len()
+    }
 }
 
-impl ApiClient {
-    /// Create a new API client.
-    pub fn new(config: Config) -> Self {
-        ApiClient { config }
+/// Larger, slower store behind a `TieredCache`.
+#[allow(dead_code)]
+pub trait L2Backend<T>: Send + Sync {
+    fn get(&self, key: &str) -> Option<T>;
+    fn set(&self, key: &str, value: T);
+}
+
+/// Two-tier cache: an in-memory L1 `CacheService` in front of an `L2Backend`.
+///
+/// Reads check L1 first and promote L2 hits into L1; writes go to both tiers.
+#[allow(dead_code)]
+pub struct TieredCache<T: Clone> {
+    l1: CacheService<T>,
+    l2: Box<dyn L2Backend<T>>,
+}
+
+#[allow(dead_code)]
+impl<T: Clone> TieredCache<T> {
+    /// Create a tiered cache from an L1 cache and an L2 backend.
+    pub fn new(l1: CacheService<T>, l2: Box<dyn L2Backend<T>>) -> Self {
+        TieredCache { l1, l2 }
     }
 
-    /// Make a GET request.
-    pub async fn get(&self, path: &str) -> Result<HashMap<String, String>, String> {
-        let url = self.build_url(path);
-        self.request("GET", &url, None).await
+    /// Get a value from L1, falling back to L2 and promoting what it finds.
+    pub fn get(&self, key: &str) -> Option<T> {
+        if let Some(value) = self.l1.get(key) {
+            return Some(value);
+        }
+        let value = self.l2.get(key)?;
+        self.l1.set(key.to_string(), value.clone());
+        Some(value)
     }
 
-    /// Make a POST request.
-    pub async fn post(&self, path: &str, data: HashMap<String, String>) -> Result<HashMap<String, String>, String> {
-        let url = self.build_url(path);
-        self.request("POST", &url, Some(data)).await
+    /// Store a value in both tiers.
+    pub fn set(&self, key: &str, value: T) {
+        self.l2.set(key, value.clone());
+        self.l1.set(key.to_string(), value);
     }
 
-    /// Make a DELETE request.
-    pub async fn delete(&self, path: &str) -> Result<HashMap<String, String>, String> {
-        let url = self.build_url(path);
-        self.request("DELETE", &url, None).await
+    /// Get the L1 cache.
+    pub fn l1(&self) -> &CacheService<T> {
+        &self.l1
     }
+}
 
-    /// Build the full URL.
-    fn build_url(&self, path: &str) -> String {
-        if path.starts_with("http") {
-            path.to_string()
-        } else {
-            format!("{}{}", self.config.api_url, path)
+/// Response returned by `ApiClient` requests.
+#[allow(dead_code)]
+pub type Response = Result<HashMap<String, String>, String>;
+
+/// Outcome of an in-flight request, shared with callers coalesced onto it.
+#[derive(Default)]
+#[allow(dead_code)]
+struct InFlight {
+    result: Option<Response>,
+    wakers: Vec<Waker>,
+}
+
+#[allow(dead_code)]
+type SharedInFlight = Arc<Mutex<InFlight>>;
+
+/// Future resolving once the leading caller publishes its result.
+#[allow(dead_code)]
+struct WaitInFlight(SharedInFlight);
+
+impl Future for WaitInFlight {
+    type Output = Response;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Response> {
+        let mut state = self.0.lock().unwrap();
+        match &state.result {
+            Some(result) => Poll::Ready(result.clone()),
+            None => {
+                state.wakers.push(cx.waker().clone());
+                Poll::Pending
+            }
         }
     }
+}
 
-    /// Make an HTTP request.
-    async fn request(
+/// Publishes the leader's result, or a cancellation error if it is dropped first.
+#[allow(dead_code)]
+struct LeaderGuard<'a> {
+    in_flight: &'a Mutex<HashMap<String, SharedInFlight>>,
+    key: String,
+    slot: Option<SharedInFlight>,
+}
+
+#[allow(dead_code)]
+impl LeaderGuard<'_> {
+    fn publish(&mut self, result: Response) {
+        if let Some(slot) = self.slot.take() {
+            self.in_flight.lock().unwrap().remove(&self.key);
+            let mut state = slot.lock().unwrap();
+            state.result = Some(result);
+            for waker in state.wakers.drain(..) {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Drop for LeaderGuard<'_> {
+    fn drop(&mut self) {
+        self.publish(Err("request cancelled".to_string()));
+    }
+}
+
+/// Future that completes after being polled once.
+#[derive(Default)]
+#[allow(dead_code)]
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Error from a typed `ApiClient` call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum ApiError {
+    /// The request could not be completed.
+    Request(String),
+    /// The response was missing or had a malformed field.
+    InvalidResponse,
+    /// Pagination revisited this URL.
+    PaginationCycle(String),
+    /// A path template placeholder was never given a value.
+    MissingPathParam(String),
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::Request(e) => write!(f, "request failed: {}", e),
+            ApiError::InvalidResponse => write!(f, "invalid response"),
+            ApiError::PaginationCycle(url) => write!(f, "pagination cycle at {}", url),
+            ApiError::MissingPathParam(name) => write!(f, "missing path parameter {{{}}}", name),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// Sends a single request and returns the response fields, including `status`.
+#[allow(dead_code)]
+pub trait Transport: Send + Sync {
+    fn send(&self, method: &str, url: &str, body: Option<&HashMap<String, String>>) -> Response;
+
+    /// Send with request headers and the codec-encoded body.
+    ///
+    /// Transports that don't model the wire format get the unencoded fields.
+    fn send_with_headers(
         &self,
         method: &str,
         url: &str,
-        _data: Option<HashMap<String, String>>,
-    ) -> Result<HashMap<String, String>, String> {
-        // Simulated request
-        let mut result = HashMap::new();
-        result.insert("status".to_string(), "200".to_string());
-        result.insert("method".to_string(), method.to_string());
-        result.insert("url".to_string(), url.to_string());
-        Ok(result)
+        body: Option<&RequestBody>,
+        headers: &HashMap<String, String>,
+    ) -> Response {
+        let _ = headers;
+        self.send(method, url, body.map(|b| &b.fields))
     }
 }
 
-/// Create a data service with defaults.
-pub fn create_service<T: Clone>() -> DataService<T> {
-    DataService::with_defaults()
+/// Request body as fields and as encoded by the client's `BodyCodec`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub struct RequestBody {
+    pub fields: HashMap<String, String>,
+    pub encoded: String,
 }
 
-/// Create an API client with defaults.
-pub fn create_api_client() -> ApiClient {
-    ApiClient::new(Config::default())
+/// Converts request and response bodies to and from their wire format.
+#[allow(dead_code)]
+pub trait BodyCodec: Send + Sync {
+    /// Get the `Content-Type` sent with encoded bodies.
+    fn content_type(&self) -> &str;
+    fn encode(&self, fields: &HashMap<String, String>) -> String;
+    fn decode(&self, body: &str) -> Result<HashMap<String, String>, String>;
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Codec for flat JSON objects with string values; the default.
+///
+/// Keys are encoded in sorted order. Decoding needs the `json` feature.
+#[allow(dead_code)]
+pub struct JsonCodec;
 
-    #[test]
-    fn test_cache_service() {
+impl BodyCodec for JsonCodec {
+    fn content_type(&self) -> &str {
+        "application/json"
+    }
+
+    fn encode(&self, fields: &HashMap<String, String>) -> String {
+        let mut fields: Vec<_> = fields.iter().collect();
+        fields.sort();
+        let members: Vec<String> = fields
+            .into_iter()
+            .map(|(k, v)| format!("{}:{}", crate::json_string(k), crate::json_string(v)))
+            .collect();
+        format!("{{{}}}", members.join(","))
+    }
+
+    #[cfg(feature = "json")]
+    fn decode(&self, body: &str) -> Result<HashMap<String, String>, String> {
+        let value: serde_json::Value = serde_json::from_str(body).map_err(|e| e.to_string())?;
+        let object = value.as_object().ok_or("expected a JSON object")?;
+        object
+            .iter()
+            .map(|(k, v)| match v.as_str() {
+                Some(v) => Ok((k.clone(), v.to_string())),
+                None => Err(format!("field {} is not a string", k)),
+            })
+            .collect()
+    }
+
+    #[cfg(not(feature = "json"))]
+    fn decode(&self, _body: &str) -> Result<HashMap<String, String>, String> {
+        Err("decoding JSON bodies requires the `json` feature".to_string())
+    }
+}
+
+/// Codec for `application/x-www-form-urlencoded` bodies.
+///
+/// Keys are encoded in sorted order; decoding accepts `+` for spaces.
+#[allow(dead_code)]
+pub struct FormUrlEncodedCodec;
+
+impl BodyCodec for FormUrlEncodedCodec {
+    fn content_type(&self) -> &str {
+        "application/x-www-form-urlencoded"
+    }
+
+    fn encode(&self, fields: &HashMap<String, String>) -> String {
+        let mut fields: Vec<_> = fields.iter().collect();
+        fields.sort();
+        let pairs: Vec<String> = fields
+            .into_iter()
+            .map(|(k, v)| format!("{}={}", percent_encode(k), percent_encode(v)))
+            .collect();
+        pairs.join("&")
+    }
+
+    fn decode(&self, body: &str) -> Result<HashMap<String, String>, String> {
+        body.split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                Ok((form_decode(key)?, form_decode(value)?))
+            })
+            .collect()
+    }
+}
+
+/// Undo form encoding: `+` becomes a space and `%XX` a byte.
+#[allow(dead_code)]
+fn form_decode(value: &str) -> Result<String, String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = rest.get(..2).and_then(|h| std::str::from_utf8(h).ok());
+                let decoded = hex.and_then(|h| u8::from_str_radix(h, 16).ok());
+                bytes.push(decoded.ok_or_else(|| format!("invalid escape in {:?}", value))?);
+                rest = &rest[2..];
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+/// Header carrying the key that lets a server deduplicate retried POSTs.
+#[allow(dead_code)]
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Header identifying the client, set from `Config::user_agent`.
+#[allow(dead_code)]
+pub const USER_AGENT_HEADER: &str = "User-Agent";
+
+/// Header naming the request body's format, set from the client's `BodyCodec`.
+#[allow(dead_code)]
+pub const CONTENT_TYPE_HEADER: &str = "Content-Type";
+
+/// Header carrying a cached ETag on a conditional GET.
+#[allow(dead_code)]
+pub const IF_NONE_MATCH_HEADER: &str = "If-None-Match";
+
+/// Transport that answers every request with a 200.
+#[allow(dead_code)]
+pub struct SimulatedTransport;
+
+impl Transport for SimulatedTransport {
+    fn send(&self, method: &str, url: &str, _body: Option<&HashMap<String, String>>) -> Response {
+        let mut result = HashMap::new();
+        result.insert("status".to_string(), "200".to_string());
+        result.insert("method".to_string(), method.to_string());
+        result.insert("url".to_string(), url.to_string());
+        Ok(result)
+    }
+}
+
+/// One request issued by `ApiClient::batch`.
+#[derive(Debug, Clone, PartialEq)]
+#[allow(dead_code)]
+pub struct RequestSpec {
+    pub method: String,
+    pub path: String,
+    pub body: Option<HashMap<String, String>>,
+}
+
+#[allow(dead_code)]
+impl RequestSpec {
+    /// Describe a GET request.
+    pub fn get(path: impl Into<String>) -> Self {
+        RequestSpec {
+            method: "GET".to_string(),
+            path: path.into(),
+            body: None,
+        }
+    }
+
+    /// Describe a POST request with a body.
+    pub fn post(path: impl Into<String>, body: HashMap<String, String>) -> Self {
+        RequestSpec {
+            method: "POST".to_string(),
+            path: path.into(),
+            body: Some(body),
+        }
+    }
+
+    /// Describe a DELETE request.
+    pub fn delete(path: impl Into<String>) -> Self {
+        RequestSpec {
+            method: "DELETE".to_string(),
+            path: path.into(),
+            body: None,
+        }
+    }
+}
+
+/// Request path built from a template such as `/tasks/{id}/status`.
+///
+/// Values given to `param` are percent-encoded as a single segment, so an id
+/// containing `/` or `?` cannot change the route.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub struct Path {
+    template: String,
+    params: HashMap<String, String>,
+}
+
+#[allow(dead_code)]
+impl Path {
+    /// Start a path from a template with `{name}` placeholders.
+    pub fn new(template: impl Into<String>) -> Self {
+        Path {
+            template: template.into(),
+            params: HashMap::new(),
+        }
+    }
+
+    /// Substitute `value` for the `{name}` placeholder.
+    pub fn param(mut self, name: &str, value: impl AsRef<str>) -> Self {
+        self.params.insert(name.to_string(), percent_encode(value.as_ref()));
+        self
+    }
+
+    /// Render the path to pass to `get`, `post` or `delete`.
+    ///
+    /// A `{` without a closing `}` is kept as literal text.
+    pub fn build(&self) -> Result<String, ApiError> {
+        let mut out = String::with_capacity(self.template.len());
+        let mut rest = self.template.as_str();
+        while let Some(start) = rest.find('{') {
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            let name = &rest[start + 1..start + len];
+            let value = self
+                .params
+                .get(name)
+                .ok_or_else(|| ApiError::MissingPathParam(name.to_string()))?;
+            out.push_str(&rest[..start]);
+            out.push_str(value);
+            rest = &rest[start + len + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+/// Percent-encode everything except RFC 3986 unreserved characters.
+#[allow(dead_code)]
+fn percent_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            let _ = write!(out, "%{:02X}", byte);
+        }
+    }
+    out
+}
+
+/// Requests `ApiClient::batch` keeps in flight at once by default.
+#[allow(dead_code)]
+const DEFAULT_BATCH_CONCURRENCY: usize = 8;
+
+/// HTTP client for API requests.
+#[allow(dead_code)]
+pub struct ApiClient {
+    config: Config,
+    transport: Box<dyn Transport>,
+    requests: Mutex<HashMap<String, u64>>,
+    windowed: WindowedMetrics,
+    rng: Mutex<Box<dyn Rng + Send>>,
+    in_flight: Mutex<HashMap<String, SharedInFlight>>,
+    batch_concurrency: usize,
+    etag_cache: Option<CacheService<HashMap<String, String>>>,
+    codec: Box<dyn BodyCodec>,
+}
+
+#[allow(dead_code)]
+impl ApiClient {
+    /// Create a new API client.
+    pub fn new(config: Config) -> Self {
+        ApiClient {
+            config,
+            transport: Box::new(SimulatedTransport),
+            requests: Mutex::new(HashMap::new()),
+            windowed: WindowedMetrics::new(),
+            rng: Mutex::new(Box::new(SeededRng::from_time())),
+            in_flight: Mutex::new(HashMap::new()),
+            batch_concurrency: DEFAULT_BATCH_CONCURRENCY,
+            etag_cache: None,
+            codec: Box::new(JsonCodec),
+        }
+    }
+
+    /// Encode request bodies and decode response bodies with `codec` instead of JSON.
+    pub fn with_codec(mut self, codec: impl BodyCodec + 'static) -> Self {
+        self.codec = Box::new(codec);
+        self
+    }
+
+    /// Decode a response's `body` field with the configured codec.
+    pub fn decode_body(
+        &self,
+        response: &HashMap<String, String>,
+    ) -> Result<HashMap<String, String>, ApiError> {
+        let body = response.get("body").ok_or(ApiError::InvalidResponse)?;
+        self.codec.decode(body).map_err(|_| ApiError::InvalidResponse)
+    }
+
+    /// Cache GET responses carrying an `etag` field for `ttl` and revalidate them.
+    ///
+    /// Later GETs for a cached URL send `If-None-Match`; a 304 returns the cached
+    /// response and restarts its TTL, while a 200 replaces it.
+    pub fn with_etag_cache(mut self, ttl: Duration) -> Self {
+        self.etag_cache = Some(CacheService::new(ttl));
+        self
+    }
+
+    /// Set how many requests `batch` keeps in flight at once.
+    pub fn with_batch_concurrency(mut self, limit: usize) -> Self {
+        self.batch_concurrency = limit.max(1);
+        self
+    }
+
+    /// Bucket requests with the given windowed metrics, e.g. to inject a clock.
+    pub fn with_windowed_metrics(mut self, windowed: WindowedMetrics) -> Self {
+        self.windowed = windowed;
+        self
+    }
+
+    /// Get the time-bucketed request counts.
+    pub fn windowed_metrics(&self) -> &WindowedMetrics {
+        &self.windowed
+    }
+
+    /// Send requests through the given transport.
+    pub fn with_transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Box::new(transport);
+        self
+    }
+
+    /// Use the given RNG for backoff jitter.
+    pub fn with_rng(mut self, rng: impl Rng + Send + 'static) -> Self {
+        self.rng = Mutex::new(Box::new(rng));
+        self
+    }
+
+    /// Get the delay before retry number `attempt` under the configured strategy.
+    pub fn backoff_delay(&self, attempt: u32) -> Duration {
+        let mut rng = self.rng.lock().unwrap();
+        self.config.backoff.delay(self.config.retry_delay, attempt, rng.as_mut())
+    }
+
+    /// Get the config.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Render request counters in Prometheus text exposition format.
+    pub fn metrics_prometheus(&self) -> String {
+        let requests = self.requests.lock().unwrap();
+        let mut samples: Vec<(String, u64)> = requests
+            .iter()
+            .map(|(method, count)| (format!("{{method=\"{}\"}}", method), *count))
+            .collect();
+        samples.sort();
+        let mut out = String::new();
+        push_metric(
+            &mut out,
+            "api_requests_total",
+            "counter",
+            "API requests issued, by method.",
+            &samples,
+        );
+        out
+    }
+
+    /// Make a GET request; concurrent GETs for the same URL share one request.
+    pub async fn get(&self, path: &str) -> Result<HashMap<String, String>, String> {
+        let url = self.build_url(path);
+        self.coalesced("GET", &url).await
+    }
+
+    /// Make a POST request under a fresh idempotency key that every retry reuses.
+    pub async fn post(&self, path: &str, data: HashMap<String, String>) -> Result<HashMap<String, String>, String> {
+        let key = self.idempotency_key();
+        self.post_with_idempotency_key(path, data, &key).await
+    }
+
+    /// Make a POST request sending `key` as its `Idempotency-Key` header.
+    pub async fn post_with_idempotency_key(
+        &self,
+        path: &str,
+        data: HashMap<String, String>,
+        key: &str,
+    ) -> Result<HashMap<String, String>, String> {
+        let url = self.build_url(path);
+        let headers = HashMap::from([(IDEMPOTENCY_KEY_HEADER.to_string(), key.to_string())]);
+        self.request("POST", &url, Some(data), headers).await
+    }
+
+    /// Generate a random 128-bit idempotency key as hex.
+    fn idempotency_key(&self) -> String {
+        let mut rng = self.rng.lock().unwrap();
+        format!("{:016x}{:016x}", rng.next_u64(), rng.next_u64())
+    }
+
+    /// Make a DELETE request.
+    pub async fn delete(&self, path: &str) -> Result<HashMap<String, String>, String> {
+        let url = self.build_url(path);
+        self.request("DELETE", &url, None, HashMap::new()).await
+    }
+
+    /// Issue `requests` with at most `batch_concurrency` in flight, returning results in input order.
+    ///
+    /// A failed request is reported in its slot and does not stop the others.
+    /// GETs share in-flight requests exactly like `get`.
+    pub async fn batch(
+        &self,
+        requests: Vec<RequestSpec>,
+    ) -> Vec<Result<HashMap<String, String>, ApiError>> {
+        type Pending<'a> = Pin<Box<dyn Future<Output = Response> + 'a>>;
+        let mut results: Vec<Option<Result<HashMap<String, String>, ApiError>>> =
+            requests.iter().map(|_| None).collect();
+        let mut queued = requests.into_iter().enumerate();
+        let mut running: Vec<(usize, Pending<'_>)> = Vec::new();
+        std::future::poll_fn(|cx| loop {
+            while running.len() < self.batch_concurrency {
+                let Some((slot, spec)) = queued.next() else {
+                    break;
+                };
+                running.push((slot, Box::pin(self.send_spec(spec))));
+            }
+            let before = running.len();
+            running.retain_mut(|(slot, fut)| match fut.as_mut().poll(cx) {
+                Poll::Ready(result) => {
+                    results[*slot] = Some(result.map_err(ApiError::Request));
+                    false
+                }
+                Poll::Pending => true,
+            });
+            if running.is_empty() && queued.len() == 0 {
+                return Poll::Ready(());
+            }
+            if running.len() == before || queued.len() == 0 {
+                return Poll::Pending;
+            }
+        })
+        .await;
+        results.into_iter().map(|r| r.expect("every batch slot completes")).collect()
+    }
+
+    /// Send one batched request the way the matching single-request method would.
+    async fn send_spec(&self, spec: RequestSpec) -> Response {
+        let url = self.build_url(&spec.path);
+        match (spec.method.as_str(), spec.body) {
+            ("GET", None) => self.coalesced("GET", &url).await,
+            ("POST", Some(body)) => self.post(&url, body).await,
+            (method, body) => self.request(method, &url, body, HashMap::new()).await,
+        }
+    }
+
+    /// Build the full URL, joining with exactly one slash; absolute URLs pass through.
+    fn build_url(&self, path: &str) -> String {
+        if path.starts_with("http://") || path.starts_with("https://") {
+            path.to_string()
+        } else {
+            let base = self.config.api_url.trim_end_matches('/');
+            format!("{}/{}", base, path.trim_start_matches('/'))
+        }
+    }
+
+    /// Make a request, or join an identical one already in flight.
+    async fn coalesced(&self, method: &str, url: &str) -> Response {
+        let key = format!("{} {}", method, url);
+        let (slot, leader) = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get(&key) {
+                Some(slot) => (slot.clone(), false),
+                None => {
+                    let slot = SharedInFlight::default();
+                    in_flight.insert(key.clone(), slot.clone());
+                    (slot, true)
+                }
+            }
+        };
+        if !leader {
+            return WaitInFlight(slot).await;
+        }
+        let mut guard = LeaderGuard {
+            in_flight: &self.in_flight,
+            key,
+            slot: Some(slot),
+        };
+        let result = match method {
+            "GET" => self.conditional_get(url).await,
+            _ => self.request(method, url, None, HashMap::new()).await,
+        };
+        guard.publish(result.clone());
+        result
+    }
+
+    /// GET `url`, revalidating a cached response by ETag when the ETag cache is enabled.
+    async fn conditional_get(&self, url: &str) -> Response {
+        let Some(cache) = &self.etag_cache else {
+            return self.request("GET", url, None, HashMap::new()).await;
+        };
+        let cached = cache.get(url);
+        let mut headers = HashMap::new();
+        if let Some(etag) = cached.as_ref().and_then(|c| c.get("etag")) {
+            headers.insert(IF_NONE_MATCH_HEADER.to_string(), etag.clone());
+        }
+        let response = self.request("GET", url, None, headers).await?;
+        match (response.get("status").map(String::as_str), cached) {
+            (Some("304"), Some(cached)) => {
+                cache.set(url.to_string(), cached.clone());
+                Ok(cached)
+            }
+            (Some("200"), _) if response.contains_key("etag") => {
+                cache.set(url.to_string(), response.clone());
+                Ok(response)
+            }
+            _ => {
+                cache.delete(url);
+                Ok(response)
+            }
+        }
+    }
+
+    /// Make an HTTP request; retries resend the same `headers`.
+    async fn request(
+        &self,
+        method: &str,
+        url: &str,
+        data: Option<HashMap<String, String>>,
+        headers: HashMap<String, String>,
+    ) -> Result<HashMap<String, String>, String> {
+        // Simulated network round-trip: hand control back once, as real I/O would.
+        YieldNow::default().await;
+
+        let result = self.send_request(method, url, data, headers);
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!(
+            "api_request",
+            method,
+            url,
+            status = tracing::field::Empty,
+            error = tracing::field::Empty,
+        );
+        #[cfg(feature = "tracing")]
+        let result = tracing::Instrument::instrument(result, span.clone());
+        let result = result.await;
+        #[cfg(feature = "tracing")]
+        match &result {
+            Ok(response) => span.record("status", response["status"].as_str()),
+            Err(e) => span.record("error", e.as_str()),
+        };
+        result
+    }
+
+    /// Send one request through the transport with retries, inside the caller's span.
+    async fn send_request(
+        &self,
+        method: &str,
+        url: &str,
+        data: Option<HashMap<String, String>>,
+        headers: HashMap<String, String>,
+    ) -> Result<HashMap<String, String>, String> {
+        *self.requests.lock().unwrap().entry(method.to_string()).or_insert(0) += 1;
+        self.windowed.record();
+
+        let body = data.map(|fields| RequestBody {
+            encoded: self.codec.encode(&fields),
+            fields,
+        });
+        let body = body.as_ref();
+        let user_agent = (USER_AGENT_HEADER.to_string(), self.config.user_agent.clone());
+        let mut merged = HashMap::from([user_agent]);
+        merged.extend(self.config.default_headers.clone());
+        if body.is_some() {
+            let content_type = self.codec.content_type().to_string();
+            merged.insert(CONTENT_TYPE_HEADER.to_string(), content_type);
+        }
+        merged.extend(headers);
+        let headers = &merged;
+        let send = || async move {
+            let result = self.transport.send_with_headers(method, url, body, headers);
+            if let Err(e) = &result {
+                log_at!(warn, "{} {} failed: {}", method, url, e);
+            }
+            result
+        };
+        let delay = |attempt| self.backoff_delay(attempt);
+        let result = retry_async_with_delays(self.config.retries + 1, delay, send).await?;
+        log_at!(debug, "{} {} -> {}", method, url, result["status"]);
+        Ok(result)
+    }
+
+    /// Ping the health path; `Ok(true)` only for a 2xx status, `Ok(false)` for any other status.
+    ///
+    /// Transport failures and responses without a numeric `status` are errors.
+    pub async fn health_check(&self) -> Result<bool, ApiError> {
+        let response = self
+            .get(&self.config.health_path)
+            .await
+            .map_err(ApiError::Request)?;
+        let status: u16 = response
+            .get("status")
+            .and_then(|s| s.parse().ok())
+            .ok_or(ApiError::InvalidResponse)?;
+        Ok((200..300).contains(&status))
+    }
+
+    /// GET `path` and every page after it, collecting each page's `items`.
+    ///
+    /// Each response `body` must be a JSON object with an `items` array; the
+    /// next page's URL is read from `next_key`, and a missing or null value ends
+    /// the walk. Revisiting a URL is an error.
+    #[cfg(feature = "json")]
+    pub async fn get_all_pages(
+        &self,
+        path: &str,
+        next_key: &str,
+    ) -> Result<Vec<serde_json::Value>, ApiError> {
+        let mut items = Vec::new();
+        let mut visited = HashSet::new();
+        let mut next = Some(self.build_url(path));
+        while let Some(url) = next {
+            if !visited.insert(url.clone()) {
+                return Err(ApiError::PaginationCycle(url));
+            }
+            let response = self.get(&url).await.map_err(ApiError::Request)?;
+            let body: serde_json::Value = response
+                .get("body")
+                .and_then(|b| b.parse().ok())
+                .ok_or(ApiError::InvalidResponse)?;
+            let page = body
+                .get("items")
+                .and_then(|i| i.as_array())
+                .ok_or(ApiError::InvalidResponse)?;
+            items.extend(page.iter().cloned());
+            next = body.get(next_key).and_then(|n| n.as_str()).map(|n| self.build_url(n));
+        }
+        Ok(items)
+    }
+}
+
+/// Create a data service with defaults.
+#[allow(dead_code)]
+pub fn create_service<T: Clone>() -> DataService<T> {
+    DataService::with_defaults()
+}
+
+/// Create an API client with defaults.
+#[allow(dead_code)]
+pub fn create_api_client() -> ApiClient {
+    ApiClient::new(Config::default())
+}
+
+/// Create an API client configured from the environment.
+#[allow(dead_code)]
+pub fn create_api_client_from_env() -> ApiClient {
+    ApiClient::new(Config::from_env())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    /// Drive a future to completion; the simulated requests never actually wait.
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        let mut fut = std::pin::pin!(fut);
+        let mut cx = std::task::Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+                return out;
+            }
+        }
+    }
+
+    #[test]
+    fn test_cache_service() {
         let cache: CacheService<String> = CacheService::new(Duration::from_secs(60));
         cache.set("key".to_string(), "value".to_string());
         assert_eq!(cache.get("key"), Some("value".to_string()));
     }
+
+    #[test]
+    fn test_cache_set_lazy_runs_once() {
+        let cache: Arc<CacheService<i32>> = Arc::new(CacheService::new(Duration::from_secs(60)));
+        let runs = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        {
+            let runs = runs.clone();
+            cache.set_lazy("answer".to_string(), move || {
+                runs.fetch_add(1, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(20));
+                42
+            });
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        assert_eq!(cache.peek("answer"), None);
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let cache = cache.clone();
+                std::thread::spawn(move || cache.get("answer"))
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), Some(42));
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.peek("answer"), Some(42));
+
+        cache.set_lazy("later".to_string(), || 1);
+        cache.set("later".to_string(), 2);
+        assert_eq!(cache.get("later"), Some(2));
+    }
+
+    #[test]
+    fn test_cache_set_lazy_panic_reads_as_miss() {
+        let cache: Arc<CacheService<i32>> = Arc::new(CacheService::new(Duration::from_secs(60)));
+        cache.set_lazy("boom".to_string(), || panic!("thunk failed"));
+        let first = {
+            let cache = cache.clone();
+            std::thread::spawn(move || cache.get("boom")).join()
+        };
+        assert!(first.is_err());
+
+        assert_eq!(cache.get("boom"), None);
+        assert!(cache.lazy.lock().unwrap().is_empty());
+        cache.set("boom".to_string(), 7);
+        assert_eq!(cache.get("boom"), Some(7));
+    }
+
+    #[test]
+    fn test_cache_buffered_set() {
+        let cache: CacheService<i32> = CacheService::new(Duration::from_secs(60)).with_buffer_threshold(3);
+        cache.set_buffered("a".to_string(), 1);
+        cache.set_buffered("a".to_string(), 2);
+        assert_eq!(cache.get("a"), Some(2));
+        assert!(cache.cache.lock().unwrap().is_empty());
+
+        cache.set_buffered("b".to_string(), 3);
+        assert!(cache.buffer.lock().unwrap().is_empty());
+        assert_eq!(cache.get("a"), Some(2));
+
+        cache.set_buffered("c".to_string(), 4);
+        cache.flush();
+        assert_eq!(cache.cache.lock().unwrap().len(), 3);
+        assert_eq!(cache.get("c"), Some(4));
+    }
+
+    #[test]
+    fn test_cache_negative_entry_expires() {
+        let clock = Arc::new(MockClock::new());
+        let cache: CacheService<i32> = CacheService::new(Duration::from_secs(60)).with_clock(clock.clone());
+        cache.set("user:1".to_string(), 1);
+        cache.set_negative("user:1".to_string(), Duration::from_millis(50));
+        assert_eq!(cache.get_result("user:1"), CacheResult::NegativeHit);
+        assert_eq!(cache.get("user:1"), None);
+        assert_eq!(cache.get_result("user:2"), CacheResult::Miss);
+
+        clock.advance(Duration::from_millis(49));
+        assert_eq!(cache.get_result("user:1"), CacheResult::NegativeHit);
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(cache.get_result("user:1"), CacheResult::Miss);
+
+        cache.set_negative("user:1".to_string(), Duration::from_millis(50));
+        cache.set("user:1".to_string(), 2);
+        assert_eq!(cache.get_result("user:1"), CacheResult::Hit(2));
+    }
+
+    #[derive(Default)]
+    struct MockL2 {
+        entries: Mutex<HashMap<String, i32>>,
+        reads: AtomicU64,
+    }
+
+    impl L2Backend<i32> for Arc<MockL2> {
+        fn get(&self, key: &str) -> Option<i32> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            self.entries.lock().unwrap().get(key).copied()
+        }
+
+        fn set(&self, key: &str, value: i32) {
+            self.entries.lock().unwrap().insert(key.to_string(), value);
+        }
+    }
+
+    #[test]
+    fn test_tiered_cache_promotes_and_writes_through() {
+        let l2 = Arc::new(MockL2::default());
+        l2.entries.lock().unwrap().insert("cold".to_string(), 1);
+        let cache = TieredCache::new(CacheService::new(Duration::from_secs(60)), Box::new(l2.clone()));
+
+        assert_eq!(cache.l1().peek("cold"), None);
+        assert_eq!(cache.get("cold"), Some(1));
+        assert_eq!(cache.l1().peek("cold"), Some(1));
+        assert_eq!(cache.get("cold"), Some(1));
+        assert_eq!(l2.reads.load(Ordering::SeqCst), 1);
+
+        cache.set("hot", 2);
+        assert_eq!(l2.entries.lock().unwrap().get("hot"), Some(&2));
+        assert_eq!(cache.get("hot"), Some(2));
+        assert_eq!(cache.get("missing"), None);
+        assert_eq!(l2.reads.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_cache_get_with_age() {
+        let clock = Arc::new(MockClock::new());
+        let cache: CacheService<String> = CacheService::new(Duration::from_millis(5)).with_clock(clock.clone());
+        cache.set("key".to_string(), "value".to_string());
+        let (_, first_age) = cache.get_with_age("key").unwrap();
+
+        clock.advance(Duration::from_millis(10));
+        let (value, later_age) = cache.get_with_age("key").unwrap();
+        assert_eq!(value, "value");
+        assert!(later_age > first_age);
+        assert!(later_age >= cache.ttl());
+        assert_eq!(cache.get("key"), None);
+        assert!(cache.get_with_age("missing").is_none());
+    }
+
+    #[test]
+    fn test_cache_peek_does_not_slide_ttl() {
+        let clock = Arc::new(MockClock::new());
+        let cache: CacheService<i32> = CacheService::new(Duration::from_millis(100))
+            .with_sliding_ttl(true)
+            .with_clock(clock.clone());
+        cache.set("key".to_string(), 1);
+
+        clock.advance(Duration::from_millis(60));
+        assert_eq!(cache.get("key"), Some(1));
+        clock.advance(Duration::from_millis(60));
+        assert_eq!(cache.peek("key"), Some(1));
+        clock.advance(Duration::from_millis(60));
+        assert_eq!(cache.peek("key"), None);
+    }
+
+    #[test]
+    fn test_cache_expires_with_mock_clock() {
+        let clock = Arc::new(MockClock::new());
+        let cache: CacheService<i32> = CacheService::new(Duration::from_secs(300)).with_clock(clock.clone());
+        cache.set("key".to_string(), 1);
+        cache.set_buffered("buffered".to_string(), 2);
+
+        clock.advance(Duration::from_secs(299));
+        assert_eq!(cache.get("key"), Some(1));
+        assert_eq!(cache.get("buffered"), Some(2));
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.get("key"), None);
+        assert_eq!(cache.get("buffered"), None);
+        assert_eq!(cache.cleanup_expired(), 1);
+    }
+
+    #[test]
+    fn test_cache_invalidate_prefix() {
+        let cache: CacheService<i32> = CacheService::new(Duration::from_secs(60));
+        cache.set("user:123:profile".to_string(), 1);
+        cache.set("user:123:settings".to_string(), 2);
+        cache.set_buffered("user:123:avatar".to_string(), 3);
+        cache.set("user:456:profile".to_string(), 4);
+        cache.set("session:123".to_string(), 5);
+
+        assert_eq!(cache.invalidate_prefix("user:123:"), 3);
+        assert_eq!(cache.get("user:123:profile"), None);
+        assert_eq!(cache.get("user:123:avatar"), None);
+        assert_eq!(cache.get("user:456:profile"), Some(4));
+        assert_eq!(cache.get("session:123"), Some(5));
+    }
+
+    #[test]
+    fn test_cache_typed_keys() {
+        let cache: CacheService<String, (u32, &'static str)> = CacheService::new(Duration::from_secs(60));
+        cache.set((1, "user"), "alice".to_string());
+        cache.set((1, "team"), "core".to_string());
+        cache.set((2, "user"), "bob".to_string());
+
+        assert_eq!(cache.get(&(1, "user")), Some("alice".to_string()));
+        assert_eq!(cache.get(&(1, "team")), Some("core".to_string()));
+        assert_eq!(cache.get(&(2, "user")), Some("bob".to_string()));
+        assert!(cache.delete(&(1, "user")));
+        assert_eq!(cache.get(&(2, "user")), Some("bob".to_string()));
+    }
+
+    #[test]
+    fn test_cache_export_import() {
+        let cache: CacheService<i32> = CacheService::new(Duration::from_secs(60));
+        cache.set("a".to_string(), 1);
+        cache.set_buffered("b".to_string(), 2);
+
+        let mut exported = cache.export();
+        exported.sort();
+        assert_eq!(exported, vec![("a".to_string(), 1), ("b".to_string(), 2)]);
+
+        cache.clear();
+        assert_eq!(cache.get("a"), None);
+        cache.import(exported.clone());
+        assert_eq!(cache.get("a"), Some(1));
+
+        let warmed: CacheService<i32> = CacheService::new(Duration::from_secs(60));
+        warmed.import(exported);
+        assert_eq!(warmed.get("b"), Some(2));
+
+        let expired: CacheService<i32> = CacheService::new(Duration::ZERO);
+        expired.set("stale".to_string(), 3);
+        assert!(expired.export().is_empty());
+    }
+
+    /// Parse the value of the sample line for `series`.
+    fn metric_value(text: &str, series: &str) -> Option<u64> {
+        text.lines()
+            .filter(|line| !line.starts_with('#'))
+            .find_map(|line| line.strip_prefix(series)?.strip_prefix(' ')?.parse().ok())
+    }
+
+    #[test]
+    fn test_metrics_prometheus() {
+        let cache: CacheService<i32> = CacheService::new(Duration::from_secs(60));
+        cache.set("a".to_string(), 1);
+        cache.get("a");
+        cache.get("a");
+        cache.get("missing");
+
+        let text = cache.metrics_prometheus();
+        assert!(text.contains("# TYPE cache_hits_total counter"));
+        assert!(text.contains("# HELP cache_entries "));
+        assert_eq!(metric_value(&text, "cache_hits_total"), Some(2));
+        assert_eq!(metric_value(&text, "cache_misses_total"), Some(1));
+        assert_eq!(metric_value(&text, "cache_entries"), Some(1));
+
+        let client = create_api_client();
+        block_on(client.get("/a")).unwrap();
+        block_on(client.get("/b")).unwrap();
+        block_on(client.delete("/a")).unwrap();
+        let text = client.metrics_prometheus();
+        assert!(text.contains("# TYPE api_requests_total counter"));
+        assert_eq!(metric_value(&text, "api_requests_total{method=\"GET\"}"), Some(2));
+        assert_eq!(metric_value(&text, "api_requests_total{method=\"DELETE\"}"), Some(1));
+    }
+
+    #[test]
+    fn test_create_api_client_from_env() {
+        std::env::set_var("API_URL", "https://env.example.com");
+        std::env::set_var("API_RETRIES", "7");
+        std::env::set_var("API_TIMEOUT_SECS", "not-a-number");
+        std::env::remove_var("API_DEBUG");
+        let client = create_api_client_from_env();
+        std::env::remove_var("API_URL");
+        std::env::remove_var("API_RETRIES");
+        std::env::remove_var("API_TIMEOUT_SECS");
+
+        let config = client.config();
+        assert_eq!(config.api_url, "https://env.example.com");
+        assert_eq!(config.retries, 7);
+        assert_eq!(config.timeout, Config::default().timeout);
+        assert!(!config.debug);
+    }
+
+    #[test]
+    fn test_backoff_jitter_differs_from_exponential() {
+        let config = Config::default();
+        assert_eq!(config.backoff, BackoffStrategy::ExponentialJitter);
+
+        let client = ApiClient::new(config).with_rng(SeededRng::new(7));
+        let mut rng = SeededRng::new(7);
+        let base = client.config().retry_delay;
+        let jittered: Vec<Duration> = (0..5).map(|a| client.backoff_delay(a)).collect();
+        let exponential: Vec<Duration> = (0..5)
+            .map(|a| BackoffStrategy::Exponential.delay(base, a, &mut rng))
+            .collect();
+        assert_eq!(exponential[4], base * 16);
+        assert_ne!(jittered, exponential);
+        assert!(jittered.iter().zip(&exponential).all(|(j, e)| j <= e));
+
+        let again = ApiClient::new(Config::default()).with_rng(SeededRng::new(7));
+        assert_eq!((0..5).map(|a| again.backoff_delay(a)).collect::<Vec<_>>(), jittered);
+        assert_eq!(BackoffStrategy::Fixed.delay(base, 4, &mut rng), base);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_concurrent_gets_are_coalesced() {
+        let client = create_api_client();
+        let get = || client.get("/users");
+        let (a, b, c, d, e) = tokio::join!(get(), get(), get(), get(), get());
+        let results = [a, b, c, d, e];
+        assert!(results.iter().all(|r| r == &results[0]));
+        assert_eq!(results[0].as_ref().unwrap()["url"], "https://api.example.com/users");
+
+        let text = client.metrics_prometheus();
+        assert!(text.contains("api_requests_total{method=\"GET\"} 1\n"));
+        assert!(client.in_flight.lock().unwrap().is_empty());
+
+        // Once the first request finished, a new GET goes out again.
+        client.get("/users").await.unwrap();
+        assert!(client.metrics_prometheus().contains("api_requests_total{method=\"GET\"} 2\n"));
+    }
+
+    #[test]
+    fn test_build_url_joining() {
+        let client = |api_url: &str| {
+            ApiClient::new(Config {
+                api_url: api_url.to_string(),
+                ..Config::default()
+            })
+        };
+        assert_eq!(client("https://x.com/").build_url("/foo"), "https://x.com/foo");
+        assert_eq!(client("https://x.com").build_url("foo"), "https://x.com/foo");
+        assert_eq!(client("https://x.com/v1/").build_url("foo/bar"), "https://x.com/v1/foo/bar");
+        assert_eq!(client("https://x.com").build_url("http://other.com/a"), "http://other.com/a");
+        assert_eq!(client("https://x.com").build_url("https://other.com/a"), "https://other.com/a");
+        assert_eq!(client("https://x.com").build_url("httpbin"), "https://x.com/httpbin");
+    }
+
+    /// Transport answering every request with a fixed status.
+    struct StatusTransport(&'static str);
+
+    impl Transport for StatusTransport {
+        fn send(&self, _method: &str, url: &str, _body: Option<&HashMap<String, String>>) -> Response {
+            let mut result = HashMap::new();
+            result.insert("status".to_string(), self.0.to_string());
+            result.insert("url".to_string(), url.to_string());
+            Ok(result)
+        }
+    }
+
+    /// Transport failing a fixed number of times before answering 200.
+    struct FlakyTransport {
+        failures_left: std::sync::atomic::AtomicU32,
+    }
+
+    impl Transport for FlakyTransport {
+        fn send(&self, method: &str, url: &str, body: Option<&HashMap<String, String>>) -> Response {
+            let left = self.failures_left.load(Ordering::SeqCst);
+            if left > 0 {
+                self.failures_left.store(left - 1, Ordering::SeqCst);
+                return Err("connection reset".to_string());
+            }
+            SimulatedTransport.send(method, url, body)
+        }
+    }
+
+    #[test]
+    fn test_api_client_retries_transport_errors() {
+        let flaky = |failures| FlakyTransport {
+            failures_left: std::sync::atomic::AtomicU32::new(failures),
+        };
+        let config = Config {
+            retries: 2,
+            backoff: BackoffStrategy::Fixed,
+            retry_delay: Duration::ZERO,
+            ..Config::default()
+        };
+        let client = ApiClient::new(config.clone()).with_transport(flaky(2));
+        assert_eq!(block_on(client.delete("/a")).unwrap()["status"], "200");
+
+        let client = ApiClient::new(config).with_transport(flaky(3));
+        assert_eq!(block_on(client.delete("/a")), Err("connection reset".to_string()));
+    }
+
+    /// Transport failing every request whose URL contains `fail`.
+    #[cfg(any(feature = "async", feature = "tracing"))]
+    struct FailingPathTransport;
+
+    #[cfg(any(feature = "async", feature = "tracing"))]
+    impl Transport for FailingPathTransport {
+        fn send(&self, method: &str, url: &str, body: Option<&HashMap<String, String>>) -> Response {
+            if url.contains("fail") {
+                return Err(format!("{} unavailable", url));
+            }
+            SimulatedTransport.send(method, url, body)
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_api_client_batch_keeps_input_order() {
+        let config = Config {
+            retries: 0,
+            ..Config::default()
+        };
+        let client = ApiClient::new(config)
+            .with_transport(FailingPathTransport)
+            .with_batch_concurrency(2);
+        let requests = vec![
+            RequestSpec::get("/a"),
+            RequestSpec::get("/fail/1"),
+            RequestSpec::post("/b", HashMap::new()),
+            RequestSpec::delete("/fail/2"),
+            RequestSpec::get("/c"),
+        ];
+        let results = client.batch(requests).await;
+        assert_eq!(results.len(), 5);
+        let url = |i: usize| results[i].as_ref().unwrap()["url"].clone();
+        assert_eq!(url(0), "https://api.example.com/a");
+        assert_eq!(results[2].as_ref().unwrap()["method"], "POST");
+        assert_eq!(url(4), "https://api.example.com/c");
+        assert_eq!(
+            results[1],
+            Err(ApiError::Request("https://api.example.com/fail/1 unavailable".to_string()))
+        );
+        assert!(matches!(&results[3], Err(ApiError::Request(e)) if e.contains("fail/2")));
+        assert!(client.batch(Vec::new()).await.is_empty());
+    }
+
+    /// Transport recording each request's idempotency key, failing the first `failures` sends.
+    struct KeyRecordingTransport {
+        keys: Mutex<Vec<Option<String>>>,
+        failures: usize,
+    }
+
+    impl Transport for Arc<KeyRecordingTransport> {
+        fn send(&self, method: &str, url: &str, _body: Option<&HashMap<String, String>>) -> Response {
+            self.send_with_headers(method, url, None, &HashMap::new())
+        }
+
+        fn send_with_headers(
+            &self,
+            method: &str,
+            url: &str,
+            body: Option<&RequestBody>,
+            headers: &HashMap<String, String>,
+        ) -> Response {
+            let mut keys = self.keys.lock().unwrap();
+            keys.push(headers.get(IDEMPOTENCY_KEY_HEADER).cloned());
+            if keys.len() <= self.failures {
+                return Err("connection reset".to_string());
+            }
+            SimulatedTransport.send(method, url, body.map(|b| &b.fields))
+        }
+    }
+
+    #[test]
+    fn test_post_retries_reuse_idempotency_key() {
+        let config = Config {
+            retries: 2,
+            backoff: BackoffStrategy::Fixed,
+            retry_delay: Duration::ZERO,
+            ..Config::default()
+        };
+        let transport = Arc::new(KeyRecordingTransport {
+            keys: Mutex::new(Vec::new()),
+            failures: 2,
+        });
+        let client = ApiClient::new(config).with_transport(transport.clone());
+        block_on(client.post("/orders", HashMap::new())).unwrap();
+        block_on(client.post("/orders", HashMap::new())).unwrap();
+        block_on(client.post_with_idempotency_key("/orders", HashMap::new(), "order-7")).unwrap();
+        block_on(client.delete("/orders/7")).unwrap();
+
+        let keys = transport.keys.lock().unwrap();
+        assert_eq!(keys.len(), 6);
+        let first = keys[0].clone().unwrap();
+        assert_eq!(first.len(), 32);
+        assert_eq!(keys[1..3], [Some(first.clone()), Some(first.clone())]);
+        assert!(keys[3].is_some() && keys[3] != keys[0]);
+        assert_eq!(keys[4].as_deref(), Some("order-7"));
+        assert_eq!(keys[5], None);
+    }
+
+    #[test]
+    fn test_path_encodes_params() {
+        let path = Path::new("/tasks/{id}/status").param("id", "a/b c?");
+        assert_eq!(path.build().unwrap(), "/tasks/a%2Fb%20c%3F/status");
+        assert_eq!(Path::new("/users/{id}").param("id", "é").build().unwrap(), "/users/%C3%A9");
+
+        let client = create_api_client();
+        let response = block_on(client.get(&path.build().unwrap())).unwrap();
+        assert_eq!(response["url"], "https://api.example.com/tasks/a%2Fb%20c%3F/status");
+    }
+
+    #[test]
+    fn test_path_missing_param() {
+        let path = Path::new("/projects/{project}/tasks/{id}").param("id", "7");
+        assert_eq!(path.build(), Err(ApiError::MissingPathParam("project".to_string())));
+        assert_eq!(Path::new("/literal/{open").build().unwrap(), "/literal/{open");
+    }
+
+    /// Transport serving a versioned body and answering 304 when the ETag matches.
+    struct EtagTransport {
+        version: Mutex<u32>,
+        conditional: Mutex<Vec<Option<String>>>,
+    }
+
+    impl Transport for Arc<EtagTransport> {
+        fn send(&self, method: &str, url: &str, _body: Option<&HashMap<String, String>>) -> Response {
+            self.send_with_headers(method, url, None, &HashMap::new())
+        }
+
+        fn send_with_headers(
+            &self,
+            _method: &str,
+            _url: &str,
+            _body: Option<&RequestBody>,
+            headers: &HashMap<String, String>,
+        ) -> Response {
+            let version = *self.version.lock().unwrap();
+            let etag = format!("\"v{}\"", version);
+            let if_none_match = headers.get(IF_NONE_MATCH_HEADER).cloned();
+            self.conditional.lock().unwrap().push(if_none_match.clone());
+            let mut result = HashMap::new();
+            if if_none_match.as_ref() == Some(&etag) {
+                result.insert("status".to_string(), "304".to_string());
+            } else {
+                result.insert("status".to_string(), "200".to_string());
+                result.insert("etag".to_string(), etag);
+                result.insert("body".to_string(), format!("body v{}", version));
+            }
+            Ok(result)
+        }
+    }
+
+    #[test]
+    fn test_etag_cache_revalidates() {
+        let transport = Arc::new(EtagTransport {
+            version: Mutex::new(1),
+            conditional: Mutex::new(Vec::new()),
+        });
+        let client = create_api_client()
+            .with_transport(transport.clone())
+            .with_etag_cache(Duration::from_secs(60));
+
+        let first = block_on(client.get("/report")).unwrap();
+        assert_eq!(first["body"], "body v1");
+        let second = block_on(client.get("/report")).unwrap();
+        assert_eq!(second["body"], "body v1");
+        assert_eq!(second["status"], "200");
+
+        *transport.version.lock().unwrap() = 2;
+        let third = block_on(client.get("/report")).unwrap();
+        assert_eq!(third["body"], "body v2");
+        let v1 = Some("\"v1\"".to_string());
+        assert_eq!(*transport.conditional.lock().unwrap(), vec![None, v1.clone(), v1]);
+    }
+
+    /// Headers and encoded body of one request seen by `HeaderRecordingTransport`.
+    type SentRequest = (HashMap<String, String>, Option<String>);
+
+    /// Transport recording the headers and encoded body of every request.
+    #[derive(Default)]
+    struct HeaderRecordingTransport(Mutex<Vec<SentRequest>>);
+
+    impl Transport for Arc<HeaderRecordingTransport> {
+        fn send(&self, method: &str, url: &str, _body: Option<&HashMap<String, String>>) -> Response {
+            self.send_with_headers(method, url, None, &HashMap::new())
+        }
+
+        fn send_with_headers(
+            &self,
+            method: &str,
+            url: &str,
+            body: Option<&RequestBody>,
+            headers: &HashMap<String, String>,
+        ) -> Response {
+            self.0.lock().unwrap().push((headers.clone(), body.map(|b| b.encoded.clone())));
+            SimulatedTransport.send(method, url, body.map(|b| &b.fields))
+        }
+    }
+
+    #[test]
+    fn test_user_agent_and_default_headers() {
+        let transport = Arc::new(HeaderRecordingTransport::default());
+        let config = Config {
+            user_agent: "task-sync/2.1".to_string(),
+            default_headers: HashMap::from([
+                ("X-Team".to_string(), "core".to_string()),
+                (IDEMPOTENCY_KEY_HEADER.to_string(), "default".to_string()),
+            ]),
+            ..Config::default()
+        };
+        let client = ApiClient::new(config).with_transport(transport.clone());
+        block_on(client.get("/a")).unwrap();
+        block_on(client.post_with_idempotency_key("/b", HashMap::new(), "call")).unwrap();
+
+        let sent = transport.0.lock().unwrap();
+        assert_eq!(sent[0].0[USER_AGENT_HEADER], "task-sync/2.1");
+        assert_eq!(sent[0].0["X-Team"], "core");
+        assert_eq!(sent[0].0[IDEMPOTENCY_KEY_HEADER], "default");
+        assert_eq!(sent[1].0[IDEMPOTENCY_KEY_HEADER], "call");
+        assert!(Config::default().user_agent.starts_with(concat!(env!("CARGO_PKG_NAME"), "/")));
+    }
+
+    #[test]
+    fn test_form_codec_sets_content_type() {
+        let transport = Arc::new(HeaderRecordingTransport::default());
+        let client = create_api_client()
+            .with_transport(transport.clone())
+            .with_codec(FormUrlEncodedCodec);
+        let data = HashMap::from([
+            ("name".to_string(), "Write docs".to_string()),
+            ("tag".to_string(), "a&b=c".to_string()),
+        ]);
+        block_on(client.post("/tasks", data.clone())).unwrap();
+        block_on(client.get("/tasks")).unwrap();
+
+        let sent = transport.0.lock().unwrap();
+        assert_eq!(sent[0].0[CONTENT_TYPE_HEADER], "application/x-www-form-urlencoded");
+        let encoded = sent[0].1.clone().unwrap();
+        assert_eq!(encoded, "name=Write%20docs&tag=a%26b%3Dc");
+        assert_eq!(FormUrlEncodedCodec.decode(&encoded), Ok(data));
+        assert_eq!(FormUrlEncodedCodec.decode("a=x+y&b"), Ok(HashMap::from([
+            ("a".to_string(), "x y".to_string()),
+            ("b".to_string(), String::new()),
+        ])));
+        assert!(!sent[1].0.contains_key(CONTENT_TYPE_HEADER));
+        assert_eq!(sent[1].1, None);
+    }
+
+    #[test]
+    fn test_json_codec_is_default() {
+        let transport = Arc::new(HeaderRecordingTransport::default());
+        let client = create_api_client().with_transport(transport.clone());
+        let data = HashMap::from([
+            ("b".to_string(), "say \"hi\"".to_string()),
+            ("a".to_string(), "1".to_string()),
+        ]);
+        block_on(client.post("/tasks", data)).unwrap();
+
+        let sent = transport.0.lock().unwrap();
+        assert_eq!(sent[0].0[CONTENT_TYPE_HEADER], "application/json");
+        assert_eq!(sent[0].1.as_deref(), Some(r#"{"a":"1","b":"say \"hi\""}"#));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_decode_body_with_json_codec() {
+        let client = create_api_client();
+        let encoded = JsonCodec.encode(&HashMap::from([("id".to_string(), "7".to_string())]));
+        let response = HashMap::from([("body".to_string(), encoded)]);
+        assert_eq!(client.decode_body(&response).unwrap()["id"], "7");
+        let nested = HashMap::from([("body".to_string(), r#"{"id":7}"#.to_string())]);
+        assert_eq!(client.decode_body(&nested), Err(ApiError::InvalidResponse));
+    }
+
+    #[test]
+    fn test_health_check() {
+        let healthy = create_api_client().with_transport(StatusTransport("200"));
+        assert_eq!(block_on(healthy.health_check()), Ok(true));
+
+        let unavailable = create_api_client().with_transport(StatusTransport("503"));
+        assert_eq!(block_on(unavailable.health_check()), Ok(false));
+
+        let garbled = create_api_client().with_transport(StatusTransport("oops"));
+        assert_eq!(block_on(garbled.health_check()), Err(ApiError::InvalidResponse));
+    }
+
+    /// Transport serving canned `body` values by URL.
+    #[cfg(feature = "json")]
+    struct PagesTransport(HashMap<String, &'static str>);
+
+    #[cfg(feature = "json")]
+    impl Transport for PagesTransport {
+        fn send(&self, _method: &str, url: &str, _body: Option<&HashMap<String, String>>) -> Response {
+            let body = self.0.get(url).ok_or_else(|| format!("no page at {}", url))?;
+            let mut result = HashMap::new();
+            result.insert("status".to_string(), "200".to_string());
+            result.insert("body".to_string(), body.to_string());
+            Ok(result)
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_get_all_pages() {
+        let pages = |last_next: &'static str| {
+            let mut pages = HashMap::new();
+            pages.insert(
+                "https://api.example.com/items".to_string(),
+                r#"{"items": [1, 2], "next": "/items?page=2"}"#,
+            );
+            pages.insert(
+                "https://api.example.com/items?page=2".to_string(),
+                r#"{"items": [3], "next": "https://api.example.com/items?page=3"}"#,
+            );
+            pages.insert("https://api.example.com/items?page=3".to_string(), last_next);
+            PagesTransport(pages)
+        };
+
+        let client = create_api_client().with_transport(pages(r#"{"items": [4, 5], "next": null}"#));
+        let items = block_on(client.get_all_pages("/items", "next")).unwrap();
+        let ids: Vec<i64> = items.iter().filter_map(|v| v.as_i64()).collect();
+        assert_eq!(ids, vec![1, 2, 3, 4, 5]);
+
+        let cyclic = create_api_client().with_transport(pages(r#"{"items": [], "next": "/items"}"#));
+        assert_eq!(
+            block_on(cyclic.get_all_pages("/items", "next")),
+            Err(ApiError::PaginationCycle("https://api.example.com/items".to_string()))
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_data_service_async_concurrent_access() {
+        let service = DataServiceAsync::with_defaults();
+        service.save(crate::Task::new("a".to_string(), "A".to_string())).await.unwrap();
+
+        let order = Mutex::new(Vec::new());
+        let record = |label: &'static str| order.lock().unwrap().push(label);
+        let (all, saved, found) = tokio::join!(
+            async {
+                let all = service.find_all().await;
+                record("find_all");
+                all
+            },
+            async {
+                let saved = service.save(crate::Task::new("b".to_string(), "B".to_string())).await;
+                record("save");
+                saved
+            },
+            async {
+                let found = service.find("a").await;
+                record("find");
+                found
+            },
+        );
+        assert!(!all.is_empty());
+        assert!(saved.is_ok());
+        assert_eq!(found.unwrap().name, "A");
+
+        let mut completed = order.into_inner().unwrap();
+        completed.sort();
+        assert_eq!(completed, vec!["find", "find_all", "save"]);
+        assert!(service.find("b").await.is_some());
+        assert!(service.delete("a").await);
+        assert_eq!(service.find_all().await.len(), 1);
+    }
+
+    #[test]
+    fn test_find_all_filtered() {
+        let service = create_service();
+        for (id, priority) in [("a", 1), ("b", 5), ("c", 8)] {
+            service.save(crate::Task::new(id.to_string(), id.to_uppercase()).with_priority(priority)).unwrap();
+        }
+        assert_eq!(service.find_all().len(), 3);
+
+        let urgent = service.find_all_filtered(|t| t.priority >= 5);
+        let mut urgent: Vec<String> = urgent.into_iter().map(|t| t.id).collect();
+        urgent.sort();
+        assert_eq!(urgent, vec!["b", "c"]);
+        assert!(service.find_all_filtered(|t| t.priority > 10).is_empty());
+    }
+
+    #[test]
+    fn test_save_all() {
+        let service = create_service();
+        let batch = vec![
+            crate::Task::new("a".to_string(), "A".to_string()),
+            crate::Task::new("b".to_string(), "B".to_string()),
+        ];
+        service.save_all(batch).unwrap();
+        assert!(service.find("a").is_some());
+        assert!(service.find("b").is_some());
+
+        let invalid = vec![
+            crate::Task::new("c".to_string(), "C".to_string()),
+            crate::Task::new("d".to_string(), String::new()),
+        ];
+        assert_eq!(service.save_all(invalid), Err("Entity 'd': Task name is required".to_string()));
+        assert!(service.find("c").is_none());
+        assert_eq!(service.find_all().len(), 2);
+    }
+
+    #[test]
+    fn test_change_log() {
+        let service = DataService::with_defaults().with_change_log();
+        service.save(crate::Task::new("a".to_string(), "A".to_string())).unwrap();
+        service.save(crate::Task::new("b".to_string(), "B".to_string())).unwrap();
+        assert!(service.delete("a"));
+        assert!(!service.delete("missing"));
+        service.save(crate::Task::new("a".to_string(), "A2".to_string())).unwrap();
+
+        let log = service.change_log();
+        let entries: Vec<(ChangeKind, &str)> = log.iter().map(|r| (r.kind, r.id.as_str())).collect();
+        assert_eq!(
+            entries,
+            vec![
+                (ChangeKind::Saved, "a"),
+                (ChangeKind::Saved, "b"),
+                (ChangeKind::Deleted, "a"),
+                (ChangeKind::Saved, "a"),
+            ]
+        );
+        assert!(log.windows(2).all(|w| w[0].at <= w[1].at));
+        assert!(create_service::<crate::Task>().change_log().is_empty());
+    }
+
+    #[test]
+    fn test_windowed_metrics_age_out() {
+        let clock = Arc::new(MockClock::new());
+        let metrics = WindowedMetrics::new().with_clock(clock.clone());
+        let client = create_api_client().with_windowed_metrics(metrics);
+        let start = clock.now();
+        let advance_to = |mins: u64| clock.advance(start + Duration::from_secs(mins * 60) - clock.now());
+        let minute = Duration::from_secs(60);
+
+        for _ in 0..3 {
+            block_on(client.delete("/a")).unwrap();
+        }
+        advance_to(2);
+        block_on(client.delete("/a")).unwrap();
+        block_on(client.delete("/a")).unwrap();
+
+        let windowed = client.windowed_metrics();
+        assert_eq!(windowed.requests_in_last(minute), 2);
+        assert_eq!(windowed.requests_in_last(5 * minute), 5);
+
+        advance_to(4);
+        assert_eq!(windowed.requests_in_last(3 * minute), 2);
+        assert_eq!(windowed.requests_in_last(minute), 0);
+
+        advance_to(90);
+        assert_eq!(windowed.requests_in_last(120 * minute), 0);
+    }
+
+    #[test]
+    fn test_api_client_request() {
+        let client = create_api_client();
+        let response = block_on(client.get("/tasks")).unwrap();
+        assert_eq!(response["status"], "200");
+        assert_eq!(response["method"], "GET");
+        assert_eq!(response["url"], "https://api.example.com/tasks");
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_api_client_emits_request_spans() {
+        use tracing::{span, Subscriber};
+        use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+        struct SpanNames(Arc<Mutex<Vec<String>>>);
+        impl<S: Subscriber> Layer<S> for SpanNames {
+            fn on_new_span(&self, attrs: &span::Attributes<'_>, _id: &span::Id, _ctx: Context<'_, S>) {
+                self.0.lock().unwrap().push(attrs.metadata().name().to_string());
+            }
+        }
+
+        let names = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::registry().with(SpanNames(Arc::clone(&names)));
+        let client = create_api_client();
+        tracing::subscriber::with_default(subscriber, || {
+            block_on(client.get("/a")).unwrap();
+            block_on(client.delete("/b")).unwrap();
+        });
+
+        let names = names.lock().unwrap();
+        assert_eq!(names.iter().filter(|n| *n == "api_request").count(), 2);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_api_client_records_request_outcome() {
+        use tracing::field::{Field, Visit};
+        use tracing::{span, Subscriber};
+        use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+        struct Fields(Arc<Mutex<Vec<String>>>);
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                self.0.lock().unwrap().push(format!("{}={:?}", field.name(), value));
+            }
+        }
+        struct Outcomes(Arc<Mutex<Vec<String>>>);
+        impl<S: Subscriber> Layer<S> for Outcomes {
+            fn on_record(&self, _id: &span::Id, values: &span::Record<'_>, _ctx: Context<'_, S>) {
+                values.record(&mut Fields(Arc::clone(&self.0)));
+            }
+        }
+
+        let outcomes = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::registry().with(Outcomes(Arc::clone(&outcomes)));
+        let config = Config {
+            retries: 0,
+            ..Config::default()
+        };
+        let client = ApiClient::new(config).with_transport(FailingPathTransport);
+        tracing::subscriber::with_default(subscriber, || {
+            block_on(client.get("/a")).unwrap();
+            block_on(client.get("/fail")).unwrap_err();
+        });
+
+        let outcomes = outcomes.lock().unwrap();
+        assert_eq!(
+            *outcomes,
+            vec![
+                "status=\"200\"".to_string(),
+                "error=\"https://api.example.com/fail unavailable\"".to_string(),
+            ]
+        );
+    }
 }
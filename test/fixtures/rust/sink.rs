@@ -0,0 +1,176 @@
+//! Batched delivery of task events.
+
+use crate::TaskEvent;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Longest the worker waits before re-checking for shutdown.
+const POLL: Duration = Duration::from_millis(50);
+
+/// Buffers events from a subscription and hands them to a callback in batches.
+///
+/// A batch is flushed once it holds `max_batch` events, once `interval` has
+/// passed since its first event, and when the sink is dropped. Within one
+/// batch, deleting a task that was added in the same batch cancels out: the
+/// `Added`, any `Updated` and the `Deleted` events for that id are all dropped.
+pub struct CoalescingSink {
+    stop: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl CoalescingSink {
+    /// Start delivering batches of `events` to `callback` on a background thread.
+    pub fn spawn<F>(
+        events: Receiver<TaskEvent>,
+        max_batch: usize,
+        interval: Duration,
+        callback: F,
+    ) -> Self
+    where
+        F: FnMut(Vec<TaskEvent>) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker = {
+            let stop = stop.clone();
+            std::thread::spawn(move || run(events, max_batch.max(1), interval, callback, &stop))
+        };
+        CoalescingSink {
+            stop,
+            worker: Some(worker),
+        }
+    }
+}
+
+impl Drop for CoalescingSink {
+    /// Deliver everything already emitted, then stop the worker.
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Worker loop: collect, coalesce and flush until stopped or disconnected.
+fn run<F>(
+    events: Receiver<TaskEvent>,
+    max_batch: usize,
+    interval: Duration,
+    mut callback: F,
+    stop: &AtomicBool,
+) where
+    F: FnMut(Vec<TaskEvent>),
+{
+    let mut batch = Vec::new();
+    let mut started = Instant::now();
+    loop {
+        let wait = if batch.is_empty() {
+            POLL
+        } else {
+            interval.saturating_sub(started.elapsed()).min(POLL)
+        };
+        match events.recv_timeout(wait) {
+            Ok(event) => {
+                if batch.is_empty() {
+                    started = Instant::now();
+                }
+                push(&mut batch, event);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if batch.len() >= max_batch || (!batch.is_empty() && started.elapsed() >= interval) {
+            callback(std::mem::take(&mut batch));
+        }
+        if stop.load(Ordering::SeqCst) {
+            for event in events.try_iter() {
+                push(&mut batch, event);
+                if batch.len() >= max_batch {
+                    callback(std::mem::take(&mut batch));
+                }
+            }
+            break;
+        }
+    }
+    if !batch.is_empty() {
+        callback(batch);
+    }
+}
+
+/// Add an event, cancelling it against an add of the same task in this batch.
+fn push(batch: &mut Vec<TaskEvent>, event: TaskEvent) {
+    if let TaskEvent::Deleted(id) = &event {
+        let added_here = batch.iter().any(|e| matches!(e, TaskEvent::Added(t) if &t.id == id));
+        if added_here {
+            batch.retain(|e| match e {
+                TaskEvent::Added(t) | TaskEvent::Updated(t) => &t.id != id,
+                TaskEvent::Deleted(_) => true,
+            });
+            return;
+        }
+    }
+    batch.push(event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Task, TaskManager};
+    use std::sync::Mutex;
+
+    fn ids(batch: &[TaskEvent]) -> Vec<String> {
+        batch
+            .iter()
+            .map(|e| match e {
+                TaskEvent::Added(t) => format!("+{}", t.id),
+                TaskEvent::Updated(t) => format!("~{}", t.id),
+                TaskEvent::Deleted(id) => format!("-{}", id),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_coalescing_sink_batches_and_cancels() {
+        let manager = TaskManager::new();
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let batches = batches.clone();
+            manager.subscribe_batched(3, Duration::from_secs(60), move |batch| {
+                batches.lock().unwrap().push(ids(&batch))
+            })
+        };
+        for i in 1..=3 {
+            manager.add_task(Task::new(i.to_string(), "Task".to_string())).unwrap();
+        }
+        manager.add_task(Task::new("4".to_string(), "Temp".to_string())).unwrap();
+        manager.update_task("4", Some("Temp 2".to_string()), None);
+        manager.delete_task("4");
+        manager.delete_task("1");
+        drop(sink);
+
+        let batches = batches.lock().unwrap();
+        assert_eq!(*batches, vec![vec!["+1", "+2", "+3"], vec!["-1"]]);
+    }
+
+    #[test]
+    fn test_coalescing_sink_flushes_on_interval() {
+        let manager = TaskManager::new();
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let _sink = {
+            let batches = batches.clone();
+            manager.subscribe_batched(100, Duration::from_millis(20), move |batch| {
+                batches.lock().unwrap().push(batch.len())
+            })
+        };
+        manager.add_task(Task::new("1".to_string(), "Task".to_string())).unwrap();
+        manager.add_task(Task::new("2".to_string(), "Task".to_string())).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while batches.lock().unwrap().is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(*batches.lock().unwrap(), vec![2]);
+    }
+}
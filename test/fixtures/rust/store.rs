@@ -0,0 +1,508 @@
+//! Insertion-ordered task storage used by the task manager.
+//!
+//! By default tasks live in a `Vec`, so lookups by id are linear. With the
+//! `indexmap` feature they live in an `IndexMap` keyed by id instead, giving
+//! O(1) lookups while still iterating in insertion order. Both backends expect
+//! ids to be unique; `TaskManager` rejects duplicates before pushing.
+
+use crate::intern::Interner;
+use crate::Task;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, MutexGuard};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "indexmap")]
+use indexmap::IndexMap;
+
+/// Ordered collection of tasks.
+///
+/// With interning on, stored tasks keep empty metadata and their entries live
+/// in a side table keyed by a shared `Arc<str>` per distinct metadata key;
+/// reads rebuild each task's `String`-keyed metadata on the way out.
+#[derive(Debug, Clone, Default)]
+pub struct TaskStore {
+    slots: Slots,
+    interned: Option<Interned>,
+}
+
+/// Tasks in insertion order, backed by a `Vec` or, with `indexmap`, an `IndexMap`.
+#[derive(Debug, Clone, Default)]
+struct Slots {
+    #[cfg(not(feature = "indexmap"))]
+    tasks: Vec<Task>,
+    #[cfg(feature = "indexmap")]
+    tasks: IndexMap<String, Task>,
+}
+
+/// Pooled metadata keys plus the metadata of every stored task that has any.
+#[derive(Debug, Clone, Default)]
+struct Interned {
+    pool: Interner,
+    metadata: HashMap<String, Vec<(Arc<str>, String)>>,
+}
+
+impl Interned {
+    /// Move `task`'s metadata into the side table under pooled keys.
+    fn stash(&mut self, task: &mut Task) {
+        let metadata = std::mem::take(&mut task.metadata);
+        if metadata.is_empty() {
+            self.metadata.remove(&task.id);
+            return;
+        }
+        let entries = metadata.into_iter().map(|(key, value)| (self.pool.intern(&key), value));
+        self.metadata.insert(task.id.clone(), entries.collect());
+    }
+
+    /// Move `task`'s metadata back out of the side table.
+    fn unstash(&mut self, task: &mut Task) {
+        if let Some(entries) = self.metadata.remove(&task.id) {
+            task.metadata =
+                entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect();
+        }
+    }
+}
+
+/// Get `task` with its metadata, borrowing it when nothing is stashed.
+fn hydrate<'a>(interned: Option<&Interned>, task: &'a Task) -> Cow<'a, Task> {
+    match interned.and_then(|i| i.metadata.get(&task.id)) {
+        Some(entries) => {
+            let mut task = task.clone();
+            task.metadata =
+                entries.iter().map(|(key, value)| (key.to_string(), value.clone())).collect();
+            Cow::Owned(task)
+        }
+        None => Cow::Borrowed(task),
+    }
+}
+
+impl TaskStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pool metadata keys so each distinct key is allocated once, including for stored tasks.
+    pub fn enable_interning(&mut self) {
+        if self.interned.is_some() {
+            return;
+        }
+        let mut interned = Interned::default();
+        for task in self.slots.iter_mut() {
+            interned.stash(task);
+        }
+        self.interned = Some(interned);
+    }
+
+    /// Get the number of distinct pooled metadata keys, or 0 without interning.
+    pub fn interned_key_count(&self) -> usize {
+        self.interned.as_ref().map_or(0, |i| i.pool.len())
+    }
+
+    /// Get the number of stored tasks.
+    pub fn len(&self) -> usize {
+        self.slots.tasks.len()
+    }
+
+    /// Check if the store holds no tasks.
+    pub fn is_empty(&self) -> bool {
+        self.slots.tasks.is_empty()
+    }
+
+    /// Clone all tasks out in insertion order.
+    pub fn to_vec(&self) -> Vec<Task> {
+        self.iter().map(Cow::into_owned).collect()
+    }
+
+    /// Append a task; its ID must not already be stored.
+    pub fn push(&mut self, mut task: Task) {
+        if let Some(interned) = &mut self.interned {
+            interned.stash(&mut task);
+        }
+        self.slots.push(task);
+    }
+
+    /// Replace the stored task with the same ID, or append it if there is none.
+    pub fn put(&mut self, mut task: Task) {
+        if let Some(interned) = &mut self.interned {
+            interned.stash(&mut task);
+        }
+        match self.slots.get_mut(&task.id) {
+            Some(stored) => *stored = task,
+            None => self.slots.push(task),
+        }
+    }
+
+    /// Get a task by ID.
+    pub fn get(&self, id: &str) -> Option<Cow<'_, Task>> {
+        self.slots.get(id).map(|task| hydrate(self.interned.as_ref(), task))
+    }
+
+    /// Get a mutable task by ID.
+    pub fn get_mut(&mut self, id: &str) -> Option<TaskMut<'_>> {
+        let task = self.slots.get_mut(id)?;
+        Some(TaskMut::new(task, self.interned.as_mut()))
+    }
+
+    /// Get the task at `index` in insertion order.
+    pub fn get_index(&self, index: usize) -> Option<Cow<'_, Task>> {
+        self.slots.get_index(index).map(|task| hydrate(self.interned.as_ref(), task))
+    }
+
+    /// Find a task by ID, starting at position `hint`, and return its position.
+    pub fn find_near(&self, id: &str, hint: usize) -> Option<(usize, Cow<'_, Task>)> {
+        let (index, task) = self.slots.find_near(id, hint)?;
+        Some((index, hydrate(self.interned.as_ref(), task)))
+    }
+
+    /// Iterate over task IDs in insertion order.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.slots.iter().map(|t| t.id.as_str())
+    }
+
+    /// Iterate over tasks in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = Cow<'_, Task>> {
+        self.slots.iter().map(|task| hydrate(self.interned.as_ref(), task))
+    }
+
+    /// Run `f` on every task in insertion order.
+    pub fn for_each_mut<F: FnMut(&mut Task)>(&mut self, mut f: F) {
+        for task in self.slots.iter_mut() {
+            f(&mut TaskMut::new(task, self.interned.as_mut()));
+        }
+    }
+
+    /// Keep only the tasks matching `f`, preserving order.
+    pub fn retain<F: FnMut(&Task) -> bool>(&mut self, mut f: F) {
+        let interned = &mut self.interned;
+        self.slots.retain(|task| {
+            let keep = f(&hydrate(interned.as_ref(), task));
+            if let (false, Some(interned)) = (keep, interned.as_mut()) {
+                interned.metadata.remove(&task.id);
+            }
+            keep
+        });
+    }
+
+    /// Remove and return the oldest task.
+    pub fn pop_front(&mut self) -> Option<Task> {
+        let task = self.slots.pop_front()?;
+        Some(self.unstash(task))
+    }
+
+    /// Remove and return the newest task.
+    pub fn pop_back(&mut self) -> Option<Task> {
+        let task = self.slots.pop_back()?;
+        Some(self.unstash(task))
+    }
+
+    /// Remove and return all tasks in insertion order.
+    pub fn drain(&mut self) -> Vec<Task> {
+        let tasks = self.slots.drain();
+        tasks.into_iter().map(|task| self.unstash(task)).collect()
+    }
+
+    /// Remove all tasks; pooled metadata keys are kept.
+    pub fn clear(&mut self) {
+        self.slots = Slots::default();
+        if let Some(interned) = &mut self.interned {
+            interned.metadata.clear();
+        }
+    }
+
+    /// Give a removed task its metadata back.
+    fn unstash(&mut self, mut task: Task) -> Task {
+        if let Some(interned) = &mut self.interned {
+            interned.unstash(&mut task);
+        }
+        task
+    }
+}
+
+/// Mutable access to a stored task.
+///
+/// With interning on, the task's metadata is rebuilt for the borrow and pooled
+/// again when the guard drops.
+pub struct TaskMut<'a> {
+    task: &'a mut Task,
+    interned: Option<&'a mut Interned>,
+}
+
+impl<'a> TaskMut<'a> {
+    fn new(task: &'a mut Task, mut interned: Option<&'a mut Interned>) -> Self {
+        if let Some(interned) = interned.as_deref_mut() {
+            interned.unstash(task);
+        }
+        TaskMut { task, interned }
+    }
+}
+
+impl Deref for TaskMut<'_> {
+    type Target = Task;
+
+    fn deref(&self) -> &Task {
+        self.task
+    }
+}
+
+impl DerefMut for TaskMut<'_> {
+    fn deref_mut(&mut self) -> &mut Task {
+        self.task
+    }
+}
+
+impl Drop for TaskMut<'_> {
+    fn drop(&mut self) {
+        if let Some(interned) = self.interned.as_deref_mut() {
+            interned.stash(self.task);
+        }
+    }
+}
+
+#[cfg(not(feature = "indexmap"))]
+impl Slots {
+    /// Append a task.
+    fn push(&mut self, task: Task) {
+        self.tasks.push(task);
+    }
+
+    /// Get a task by ID.
+    fn get(&self, id: &str) -> Option<&Task> {
+        self.tasks.iter().find(|t| t.id == id)
+    }
+
+    /// Get a mutable task by ID.
+    fn get_mut(&mut self, id: &str) -> Option<&mut Task> {
+        self.tasks.iter_mut().find(|t| t.id == id)
+    }
+
+    /// Get the task at `index` in insertion order.
+    fn get_index(&self, index: usize) -> Option<&Task> {
+        self.tasks.get(index)
+    }
+
+    /// Find a task by ID, checking position `hint` before scanning, and return its position.
+    fn find_near(&self, id: &str, hint: usize) -> Option<(usize, &Task)> {
+        match self.tasks.get(hint) {
+            Some(task) if task.id == id => Some((hint, task)),
+            _ => self.tasks.iter().enumerate().find(|(_, t)| t.id == id),
+        }
+    }
+
+    /// Iterate over tasks in insertion order.
+    fn iter(&self) -> impl Iterator<Item = &Task> {
+        self.tasks.iter()
+    }
+
+    /// Iterate mutably over tasks in insertion order.
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut Task> {
+        self.tasks.iter_mut()
+    }
+
+    /// Keep only the tasks matching `f`, preserving order.
+    fn retain<F: FnMut(&Task) -> bool>(&mut self, f: F) {
+        self.tasks.retain(f);
+    }
+
+    /// Remove and return the oldest task.
+    fn pop_front(&mut self) -> Option<Task> {
+        if self.tasks.is_empty() {
+            None
+        } else {
+            Some(self.tasks.remove(0))
+        }
+    }
+
+    /// Remove and return the newest task.
+    fn pop_back(&mut self) -> Option<Task> {
+        self.tasks.pop()
+    }
+
+    /// Remove and return all tasks in insertion order.
+    fn drain(&mut self) -> Vec<Task> {
+        std::mem::take(&mut self.tasks)
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl Slots {
+    /// Append a task; its ID must not already be stored.
+    fn push(&mut self, task: Task) {
+        self.tasks.insert(task.id.clone(), task);
+    }
+
+    /// Get a task by ID.
+    fn get(&self, id: &str) -> Option<&Task> {
+        self.tasks.get(id)
+    }
+
+    /// Get a mutable task by ID.
+    fn get_mut(&mut self, id: &str) -> Option<&mut Task> {
+        self.tasks.get_mut(id)
+    }
+
+    /// Get the task at `index` in insertion order.
+    fn get_index(&self, index: usize) -> Option<&Task> {
+        self.tasks.get_index(index).map(|(_, task)| task)
+    }
+
+    /// Find a task by ID and return its position; `hint` is unused since lookups are O(1).
+    fn find_near(&self, id: &str, _hint: usize) -> Option<(usize, &Task)> {
+        self.tasks.get_full(id).map(|(index, _, task)| (index, task))
+    }
+
+    /// Iterate over tasks in insertion order.
+    fn iter(&self) -> impl Iterator<Item = &Task> {
+        self.tasks.values()
+    }
+
+    /// Iterate mutably over tasks in insertion order.
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut Task> {
+        self.tasks.values_mut()
+    }
+
+    /// Keep only the tasks matching `f`, preserving order.
+    fn retain<F: FnMut(&Task) -> bool>(&mut self, mut f: F) {
+        self.tasks.retain(|_, task| f(task));
+    }
+
+    /// Remove and return the oldest task.
+    fn pop_front(&mut self) -> Option<Task> {
+        self.tasks.shift_remove_index(0).map(|(_, task)| task)
+    }
+
+    /// Remove and return the newest task.
+    fn pop_back(&mut self) -> Option<Task> {
+        self.tasks.pop().map(|(_, task)| task)
+    }
+
+    /// Remove and return all tasks in insertion order.
+    fn drain(&mut self) -> Vec<Task> {
+        self.tasks.drain(..).map(|(_, task)| task).collect()
+    }
+}
+
+/// Lock guard that warns (with the `logging` feature) when held longer than a threshold.
+pub struct TimedGuard<'a> {
+    guard: MutexGuard<'a, TaskStore>,
+    acquired: Instant,
+    threshold: Option<Duration>,
+}
+
+impl<'a> TimedGuard<'a> {
+    /// Start timing an acquired lock; `None` disables the warning.
+    pub fn new(guard: MutexGuard<'a, TaskStore>, threshold: Option<Duration>) -> Self {
+        TimedGuard {
+            guard,
+            acquired: Instant::now(),
+            threshold,
+        }
+    }
+}
+
+impl Deref for TimedGuard<'_> {
+    type Target = TaskStore;
+
+    fn deref(&self) -> &TaskStore {
+        &self.guard
+    }
+}
+
+impl DerefMut for TimedGuard<'_> {
+    fn deref_mut(&mut self) -> &mut TaskStore {
+        &mut self.guard
+    }
+}
+
+impl Drop for TimedGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(threshold) = self.threshold {
+            let held = self.acquired.elapsed();
+            if held > threshold {
+                log_at!(warn, "task lock held for {:?} (threshold {:?})", held, threshold);
+            }
+        }
+    }
+}
+
+impl Extend<Task> for TaskStore {
+    fn extend<I: IntoIterator<Item = Task>>(&mut self, iter: I) {
+        for task in iter {
+            self.push(task);
+        }
+    }
+}
+
+impl FromIterator<Task> for TaskStore {
+    fn from_iter<I: IntoIterator<Item = Task>>(iter: I) -> Self {
+        let mut store = TaskStore::new();
+        store.extend(iter);
+        store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_lookup_and_order() {
+        let store: TaskStore = (0..100)
+            .map(|i| Task::new(format!("task-{}", i), format!("Task {}", i)))
+            .collect();
+        assert_eq!(store.get("task-42").unwrap().name, "Task 42");
+        assert!(store.get("missing").is_none());
+
+        let ids: Vec<String> = store.iter().map(|t| t.id.clone()).collect();
+        let expected: Vec<String> = (0..100).map(|i| format!("task-{}", i)).collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn test_store_pop_and_drain() {
+        let mut store: TaskStore =
+            (0..4).map(|i| Task::new(i.to_string(), "Task".to_string())).collect();
+        assert_eq!(store.pop_front().unwrap().id, "0");
+        assert_eq!(store.pop_back().unwrap().id, "3");
+        assert!(store.get("0").is_none());
+        let rest: Vec<String> = store.drain().into_iter().map(|t| t.id).collect();
+        assert_eq!(rest, vec!["1", "2"]);
+        assert!(store.is_empty());
+        assert!(store.pop_front().is_none());
+    }
+
+    #[test]
+    fn test_store_interns_metadata_keys() {
+        let mut store = TaskStore::new();
+        store.push(Task::new("a".to_string(), "A".to_string()).with_metadata("owner", "ann"));
+        store.enable_interning();
+        store.push(Task::new("b".to_string(), "B".to_string()).with_metadata("owner", "bob"));
+        let stashed = &store.interned.as_ref().unwrap().metadata;
+        assert!(Arc::ptr_eq(&stashed["a"][0].0, &stashed["b"][0].0));
+        assert_eq!(store.interned_key_count(), 1);
+
+        assert!(store.slots.iter().all(|t| t.metadata.is_empty()));
+        assert_eq!(store.get("b").unwrap().metadata["owner"], "bob");
+        store.get_mut("a").unwrap().metadata.insert("team".to_string(), "core".to_string());
+        assert_eq!(store.get("a").unwrap().metadata.len(), 2);
+        store.retain(|t| t.metadata["owner"] != "bob");
+        assert_eq!(store.drain()[0].metadata["team"], "core");
+        assert!(store.interned.as_ref().unwrap().metadata.is_empty());
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn test_store_lookup_is_keyed_by_id() {
+        let mut store: TaskStore =
+            (0..1000).map(|i| Task::new(i.to_string(), format!("Task {}", i))).collect();
+        store.retain(|t| t.id != "10");
+        store.pop_front();
+        // Lookups hash the id instead of scanning, and positions track removals.
+        assert!(store.slots.tasks.iter().all(|(key, task)| *key == task.id));
+        let (position, task) = store.find_near("999", 0).unwrap();
+        assert_eq!((position, task.name.as_str()), (997, "Task 999"));
+        assert_eq!(store.get("500").unwrap().name, "Task 500");
+        assert!(store.get("10").is_none());
+    }
+}
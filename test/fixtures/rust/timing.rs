@@ -0,0 +1,139 @@
+//! Helpers for coalescing and limiting rapid repeated calls.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[allow(dead_code)]
+type Job = Box<dyn FnOnce() + Send>;
+
+#[allow(dead_code)]
+struct DebounceState {
+    pending: Option<Job>,
+    deadline: Instant,
+    worker_running: bool,
+}
+
+/// Runs only the latest call once `delay` passes without another call.
+///
+/// The call runs on a background thread; each new call replaces the pending
+/// one and restarts the quiet period.
+#[allow(dead_code)]
+pub struct Debouncer {
+    delay: Duration,
+    state: Arc<Mutex<DebounceState>>,
+}
+
+#[allow(dead_code)]
+impl Debouncer {
+    /// Create a debouncer with the given quiet period.
+    pub fn new(delay: Duration) -> Self {
+        Debouncer {
+            delay,
+            state: Arc::new(Mutex::new(DebounceState {
+                pending: None,
+                deadline: Instant::now(),
+                worker_running: false,
+            })),
+        }
+    }
+
+    /// Schedule `f`, replacing any pending call and resetting the timer.
+    pub fn call(&self, f: impl FnOnce() + Send + 'static) {
+        let mut state = self.state.lock().unwrap();
+        state.pending = Some(Box::new(f));
+        state.deadline = Instant::now() + self.delay;
+        if state.worker_running {
+            return;
+        }
+        state.worker_running = true;
+        let shared = self.state.clone();
+        std::thread::spawn(move || loop {
+            let (job, remaining) = {
+                let mut state = shared.lock().unwrap();
+                let now = Instant::now();
+                if now >= state.deadline {
+                    state.worker_running = false;
+                    (state.pending.take(), None)
+                } else {
+                    (None, Some(state.deadline - now))
+                }
+            };
+            match remaining {
+                Some(remaining) => std::thread::sleep(remaining),
+                None => {
+                    if let Some(job) = job {
+                        job();
+                    }
+                    return;
+                }
+            }
+        });
+    }
+}
+
+/// Allows an operation at most once per interval.
+#[allow(dead_code)]
+pub struct Throttler {
+    interval: Duration,
+    last: Mutex<Option<Instant>>,
+}
+
+#[allow(dead_code)]
+impl Throttler {
+    /// Create a throttler allowing one call per `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Throttler {
+            interval,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Check whether a call may proceed now, recording it if so.
+    pub fn try_call(&self) -> bool {
+        let mut last = self.last.lock().unwrap();
+        let now = Instant::now();
+        if last.is_some_and(|at| now.duration_since(at) < self.interval) {
+            return false;
+        }
+        *last = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_debouncer_runs_once_after_quiet_period() {
+        let debouncer = Debouncer::new(Duration::from_millis(40));
+        let runs = Arc::new(AtomicUsize::new(0));
+        let last = Arc::new(AtomicUsize::new(0));
+        for i in 1..=5 {
+            let (runs, last) = (runs.clone(), last.clone());
+            debouncer.call(move || {
+                runs.fetch_add(1, Ordering::SeqCst);
+                last.store(i, Ordering::SeqCst);
+            });
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+        std::thread::sleep(Duration::from_millis(120));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(last.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn test_throttler_allows_once_per_interval() {
+        let throttler = Throttler::new(Duration::from_millis(50));
+        assert!(throttler.try_call());
+        let allowed = (0..20).filter(|_| throttler.try_call()).count();
+        assert_eq!(allowed, 0);
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(throttler.try_call());
+        assert!(!throttler.try_call());
+    }
+}
@@ -1,13 +1,42 @@
 //! Utility functions for data manipulation.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::Mutex;
+
+// Re-exported so existing `utils::` paths keep working; the binary itself uses only some.
+#[allow(unused_imports)]
+pub use crate::utils_core::{
+    camel_to_snake, camel_to_snake_unicode, chunk, chunk_exact, flatten, snake_to_camel, snake_to_camel_unicode,
+    split_into_words,
+};
+
+/// Convert snake_case to kebab-case, keeping case; any `-` already present stays.
+#[allow(dead_code)]
+pub fn snake_to_kebab(name: &str) -> String {
+    name.replace('_', "-")
+}
+
+/// Convert kebab-case to snake_case, keeping case; any `_` already present stays.
+#[allow(dead_code)]
+pub fn kebab_to_snake(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+/// Convert camelCase to kebab-case via [`camel_to_snake`].
+#[allow(dead_code)]
+pub fn camel_to_kebab(name: &str) -> String {
+    snake_to_kebab(&camel_to_snake(name))
+}
 
 /// Format data as a string.
+#[allow(dead_code)]
 pub fn format_data<T: std::fmt::Debug>(data: &T) -> String {
     format!("{:?}", data)
 }
 
 /// Validate that a value is not empty.
+#[allow(dead_code)]
 pub fn validate_not_empty(value: &str) -> Result<(), String> {
     if value.is_empty() {
         Err("Value cannot be empty".to_string())
@@ -17,6 +46,7 @@ pub fn validate_not_empty(value: &str) -> Result<(), String> {
 }
 
 /// Deep merge two HashMaps.
+#[allow(dead_code)]
 pub fn deep_merge(
     base: HashMap<String, String>,
     updates: HashMap<String, String>,
@@ -28,71 +58,100 @@ pub fn deep_merge(
     result
 }
 
-/// Flatten a nested vector.
-pub fn flatten<T: Clone>(nested: Vec<Vec<T>>) -> Vec<T> {
-    nested.into_iter().flatten().collect()
+/// Merge maps in order; later maps win on conflicting keys.
+#[allow(dead_code)]
+pub fn merge_many(maps: Vec<HashMap<String, String>>) -> HashMap<String, String> {
+    maps.into_iter().fold(HashMap::new(), deep_merge)
 }
 
-/// Split a vector into chunks.
-pub fn chunk<T: Clone>(items: Vec<T>, size: usize) -> Vec<Vec<T>> {
-    items.chunks(size).map(|c| c.to_vec()).collect()
+/// Merge maps in a parallel reduction tree.
+///
+/// Precedence is identical to [`merge_many`]: each pairwise merge keeps the
+/// left map before the right one, so later maps still win on conflicts.
+#[cfg(feature = "parallel")]
+#[allow(dead_code)]
+pub fn merge_many_parallel(maps: Vec<HashMap<String, String>>) -> HashMap<String, String> {
+    use rayon::prelude::*;
+    maps.into_par_iter().reduce(HashMap::new, deep_merge)
 }
 
 /// Safely get a value from a HashMap.
+#[allow(dead_code)]
 pub fn safe_get<'a>(map: &'a HashMap<String, String>, key: &str) -> Option<&'a String> {
     map.get(key)
 }
 
-/// Transform all keys in a HashMap.
-pub fn transform_keys<F>(map: HashMap<String, String>, transformer: F) -> HashMap<String, String>
-where
-    F: Fn(&str) -> String,
-{
-    map.into_iter().map(|(k, v)| (transformer(&k), v)).collect()
+/// Safely get several values by reference, aligned with `keys`.
+#[allow(dead_code)]
+pub fn safe_get_many<'a>(map: &'a HashMap<String, String>, keys: &[&str]) -> Vec<Option<&'a String>> {
+    keys.iter().map(|key| map.get(*key)).collect()
 }
 
-/// Convert snake_case to camelCase.
-pub fn snake_to_camel(name: &str) -> String {
-    let mut result = String::new();
-    let mut capitalize_next = false;
+/// Get a value or fall back to `default`, without allocating.
+#[allow(dead_code)]
+pub fn get_or<'a>(map: &'a HashMap<String, String>, key: &str, default: &'a str) -> &'a str {
+    map.get(key).map_or(default, String::as_str)
+}
 
-    for ch in name.chars() {
-        if ch == '_' {
-            capitalize_next = true;
-        } else if capitalize_next {
-            result.push(ch.to_ascii_uppercase());
-            capitalize_next = false;
-        } else {
-            result.push(ch);
-        }
-    }
+/// Copy only the listed keys; missing keys are ignored.
+#[allow(dead_code)]
+pub fn pick(map: &HashMap<String, String>, keys: &[&str]) -> HashMap<String, String> {
+    keys.iter()
+        .filter_map(|k| map.get_key_value(*k))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect()
+}
 
-    result
+/// Copy every key except the listed ones.
+#[allow(dead_code)]
+pub fn omit(map: &HashMap<String, String>, keys: &[&str]) -> HashMap<String, String> {
+    map.iter()
+        .filter(|(k, _)| !keys.contains(&k.as_str()))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect()
 }
 
-/// Convert camelCase to snake_case.
-pub fn camel_to_snake(name: &str) -> String {
-    let mut result = String::new();
+/// Swap keys and values.
+///
+/// Duplicate values are last-wins in iteration order; since `HashMap` order is
+/// unspecified, use `invert_multi` when values may repeat.
+#[allow(dead_code)]
+pub fn invert(map: HashMap<String, String>) -> HashMap<String, String> {
+    map.into_iter().map(|(k, v)| (v, k)).collect()
+}
 
-    for ch in name.chars() {
-        if ch.is_uppercase() {
-            if !result.is_empty() {
-                result.push('_');
-            }
-            result.push(ch.to_ascii_lowercase());
-        } else {
-            result.push(ch);
-        }
+/// Swap keys and values, keeping every key for a repeated value, sorted.
+#[allow(dead_code)]
+pub fn invert_multi(map: HashMap<String, String>) -> HashMap<String, Vec<String>> {
+    let mut inverted: HashMap<String, Vec<String>> = HashMap::new();
+    for (k, v) in map {
+        inverted.entry(v).or_default().push(k);
+    }
+    for keys in inverted.values_mut() {
+        keys.sort();
     }
+    inverted
+}
 
-    result
+/// Transform all keys in a HashMap.
+#[allow(dead_code)]
+pub fn transform_keys<F>(map: HashMap<String, String>, transformer: F) -> HashMap<String, String>
+where
+    F: Fn(&str) -> String,
+{
+    map.into_iter().map(|(k, v)| (transformer(&k), v)).collect()
 }
 
 /// Data transformer struct.
+#[allow(dead_code)]
 pub struct DataTransformer {
-    transformations: Vec<Box<dyn Fn(String) -> String>>,
+    transformations: Vec<(String, Transform)>,
 }
 
+#[allow(dead_code)]
+type Transform = Box<dyn Fn(String) -> String>;
+
+#[allow(dead_code)]
 impl DataTransformer {
     /// Create a new data transformer.
     pub fn new() -> Self {
@@ -106,14 +165,65 @@ impl DataTransformer {
     where
         F: Fn(String) -> String + 'static,
     {
-        self.transformations.push(Box::new(f));
+        self.add_named("anonymous", f)
+    }
+
+    /// Add a transformation with a stage name shown by `Debug`.
+    pub fn add_named<F>(&mut self, name: &str, f: F) -> &mut Self
+    where
+        F: Fn(String) -> String + 'static,
+    {
+        self.transformations.push((name.to_string(), Box::new(f)));
         self
     }
 
+    /// Get the number of registered transformations.
+    pub fn len(&self) -> usize {
+        self.transformations.len()
+    }
+
+    /// Check if no transformations are registered.
+    pub fn is_empty(&self) -> bool {
+        self.transformations.is_empty()
+    }
+
+    /// Add a transformation that only runs when `pred` holds for the current value.
+    pub fn add_conditional<P, F>(&mut self, pred: P, f: F) -> &mut Self
+    where
+        P: Fn(&str) -> bool + 'static,
+        F: Fn(String) -> String + 'static,
+    {
+        self.add_named("conditional", move |s| if pred(&s) { f(s) } else { s })
+    }
+
+    /// Trim surrounding whitespace.
+    pub fn trim(&mut self) -> &mut Self {
+        self.add_named("trim", |s| s.trim().to_string())
+    }
+
+    /// Lowercase the text.
+    pub fn lowercase(&mut self) -> &mut Self {
+        self.add_named("lowercase", |s| s.to_lowercase())
+    }
+
+    /// Convert to camelCase.
+    // Named after the conversion it adds, like the other presets; it doesn't convert `self`.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_camel(&mut self) -> &mut Self {
+        self.add_named("to_camel", |s| snake_to_camel(&s))
+    }
+
+    /// Convert to snake_case.
+    // Named after the conversion it adds, like the other presets; it doesn't convert `self`.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_snake(&mut self) -> &mut Self {
+        self.add_named("to_snake", |s| camel_to_snake(&s))
+    }
+
     /// Apply all transformations.
     pub fn transform(&self, data: String) -> String {
         let mut result = data;
-        for f in &self.transformations {
+        for (_, f) in &self.transformations {
             result = f(result);
         }
         result
@@ -131,7 +241,77 @@ impl Default for DataTransformer {
     }
 }
 
+impl std::fmt::Debug for DataTransformer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stages: Vec<&str> =
+            self.transformations.iter().map(|(name, _)| name.as_str()).collect();
+        f.debug_struct("DataTransformer").field("stages", &stages).finish()
+    }
+}
+
+/// Cached wrapper around a pure function.
+///
+/// Results are kept per input; with a capacity, the oldest cached input is
+/// evicted first. The function runs outside the lock, so concurrent first
+/// calls for the same input may each compute it.
+#[allow(dead_code)]
+pub struct Memoize<K, V> {
+    f: Box<dyn Fn(&K) -> V + Send + Sync>,
+    cache: Mutex<(HashMap<K, V>, VecDeque<K>)>,
+    capacity: Option<usize>,
+}
+
+#[allow(dead_code)]
+impl<K: Eq + Hash + Clone, V: Clone> Memoize<K, V> {
+    /// Wrap `f` with an unbounded cache.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&K) -> V + Send + Sync + 'static,
+    {
+        Memoize {
+            f: Box::new(f),
+            cache: Mutex::new((HashMap::new(), VecDeque::new())),
+            capacity: None,
+        }
+    }
+
+    /// Keep at most `capacity` results (at least one).
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity.max(1));
+        self
+    }
+
+    /// Get the cached result for `key`, computing it on first use.
+    pub fn get(&self, key: &K) -> V {
+        if let Some(value) = self.cache.lock().unwrap().0.get(key) {
+            return value.clone();
+        }
+        let value = (self.f)(key);
+        let (values, order) = &mut *self.cache.lock().unwrap();
+        if values.insert(key.clone(), value.clone()).is_none() {
+            order.push_back(key.clone());
+            if self.capacity.is_some_and(|cap| order.len() > cap) {
+                if let Some(oldest) = order.pop_front() {
+                    values.remove(&oldest);
+                }
+            }
+        }
+        value
+    }
+
+    /// Get the number of cached results.
+    pub fn len(&self) -> usize {
+        self.cache.lock().unwrap().0.len()
+    }
+
+    /// Check if nothing is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
 /// Process and format data.
+#[allow(dead_code)]
 pub fn process_and_format<T: std::fmt::Debug>(data: &T) -> String {
     format_data(data)
 }
@@ -151,4 +331,213 @@ mod tests {
         assert_eq!(camel_to_snake("helloWorld"), "hello_world");
         assert_eq!(camel_to_snake("someLongName"), "some_long_name");
     }
+
+    #[test]
+    fn test_split_into_words() {
+        assert_eq!(split_into_words("parseHTTPResponse2Json"), vec!["parse", "HTTP", "Response", "2", "Json"]);
+        assert_eq!(split_into_words("XMLHttpRequest"), vec!["XML", "Http", "Request"]);
+        assert_eq!(split_into_words("some_long-name here"), vec!["some", "long", "name", "here"]);
+        assert_eq!(split_into_words("__leading__and__trailing__"), vec!["leading", "and", "trailing"]);
+        assert_eq!(split_into_words("v10Beta"), vec!["v", "10", "Beta"]);
+        assert!(split_into_words("_-_").is_empty());
+
+        assert_eq!(camel_to_snake("parseHTTPResponse2Json"), "parse_http_response_2_json");
+        assert_eq!(snake_to_camel("parse_http_response"), "parseHttpResponse");
+    }
+
+    #[test]
+    fn test_kebab_conversion() {
+        assert_eq!(snake_to_kebab("max_retry_count"), "max-retry-count");
+        assert_eq!(snake_to_kebab("Max_Retry"), "Max-Retry");
+        assert_eq!(kebab_to_snake("max-retry-count"), "max_retry_count");
+        assert_eq!(kebab_to_snake("API-Key"), "API_Key");
+        assert_eq!(snake_to_kebab(""), "");
+        assert_eq!(kebab_to_snake(""), "");
+
+        assert_eq!(snake_to_kebab("mixed_sep-name"), "mixed-sep-name");
+        assert_eq!(kebab_to_snake("mixed_sep-name"), "mixed_sep_name");
+        assert_eq!(kebab_to_snake(&snake_to_kebab("log_level__debug")), "log_level__debug");
+
+        assert_eq!(camel_to_kebab("maxRetryCount"), "max-retry-count");
+        assert_eq!(camel_to_kebab("parseHTTPResponse"), "parse-http-response");
+        assert_eq!(camel_to_kebab(""), "");
+    }
+
+    #[test]
+    fn test_unicode_case_conversion() {
+        assert_eq!(snake_to_camel_unicode("über_wert"), "überWert");
+        assert_eq!(snake_to_camel_unicode("grüße_ßtraße"), "grüßeSStraße");
+        assert_eq!(snake_to_camel_unicode("café_élan"), "caféÉlan");
+        assert_eq!(snake_to_camel("über_über"), "überüber");
+
+        assert_eq!(camel_to_snake_unicode("ÜberWert"), "über_wert");
+        assert_eq!(camel_to_snake_unicode("caféÉlan"), "café_élan");
+        assert_eq!(camel_to_snake("ÜberWert"), "Über_wert");
+    }
+
+    #[test]
+    fn test_memoize_runs_once_per_input() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let square = Memoize::new(move |n: &u64| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            n * n
+        });
+        assert_eq!(square.get(&3), 9);
+        assert_eq!(square.get(&3), 9);
+        assert_eq!(square.get(&4), 16);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let bounded = Memoize::new(|s: &String| s.len()).with_capacity(2);
+        for key in ["a", "bb", "ccc"] {
+            bounded.get(&key.to_string());
+        }
+        assert_eq!(bounded.len(), 2);
+    }
+
+    #[test]
+    fn test_data_transformer_presets() {
+        let mut transformer = DataTransformer::new();
+        transformer.trim().to_camel();
+        assert_eq!(transformer.transform("  user_id_value \n".to_string()), "userIdValue");
+
+        let mut snake = DataTransformer::new();
+        snake.trim().lowercase().to_snake();
+        assert_eq!(snake.transform(" HTTP Response ".to_string()), "http_response");
+    }
+
+    #[test]
+    fn test_data_transformer_add_conditional() {
+        let mut transformer = DataTransformer::new();
+        transformer
+            .trim()
+            .add_conditional(|s| s.len() == 3 && s.chars().all(|c| c.is_ascii_alphabetic()), |s| s.to_uppercase());
+        assert_eq!(transformer.transform(" usd ".to_string()), "USD");
+        assert_eq!(transformer.transform(" dollars ".to_string()), "dollars");
+    }
+
+    #[test]
+    fn test_data_transformer_len_and_debug() {
+        let mut transformer = DataTransformer::new();
+        assert!(transformer.is_empty());
+        transformer
+            .trim()
+            .add_transformation(|s| s + "!")
+            .add_named("shout", |s| s.to_uppercase());
+        assert_eq!(transformer.len(), 3);
+        assert_eq!(
+            format!("{:?}", transformer),
+            r#"DataTransformer { stages: ["trim", "anonymous", "shout"] }"#
+        );
+
+        transformer.clear();
+        assert_eq!(transformer.len(), 0);
+        assert!(transformer.is_empty());
+    }
+
+    #[test]
+    fn test_merge_many_last_wins() {
+        let maps: Vec<HashMap<String, String>> = (0..3)
+            .map(|i| {
+                [("shared".to_string(), i.to_string()), (format!("k{}", i), i.to_string())]
+                    .into_iter()
+                    .collect()
+            })
+            .collect();
+        let merged = merge_many(maps);
+        assert_eq!(merged.len(), 4);
+        assert_eq!(merged["shared"], "2");
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_merge_many_parallel_matches_sequential() {
+        let maps: Vec<HashMap<String, String>> = (0..40)
+            .map(|i| {
+                (0..200)
+                    .map(|k| (format!("key-{}", (k * (i + 1)) % 300), format!("{}-{}", i, k)))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(merge_many_parallel(maps.clone()), merge_many(maps));
+    }
+
+    #[test]
+    fn test_get_or() {
+        let mut map = HashMap::new();
+        map.insert("a".to_string(), "1".to_string());
+
+        let present = get_or(&map, "a", "fallback");
+        assert_eq!(present, "1");
+        assert!(std::ptr::eq(present, map["a"].as_str()));
+
+        let default = "fallback";
+        let absent = get_or(&map, "b", default);
+        assert!(std::ptr::eq(absent, default));
+    }
+
+    #[test]
+    fn test_pick_and_omit() {
+        let map: HashMap<String, String> = [("id", "1"), ("name", "n"), ("token", "secret")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        let picked = pick(&map, &["id", "name", "missing"]);
+        assert_eq!(picked.len(), 2);
+        assert_eq!(picked["id"], "1");
+        assert_eq!(picked["name"], "n");
+
+        let omitted = omit(&map, &["token", "missing"]);
+        assert_eq!(omitted.len(), 2);
+        assert!(!omitted.contains_key("token"));
+        assert_eq!(omitted["name"], "n");
+    }
+
+    #[test]
+    fn test_invert() {
+        let map: HashMap<String, String> = [("a", "1"), ("b", "2")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let inverted = invert(map);
+        assert_eq!(inverted.len(), 2);
+        assert_eq!(inverted["1"], "a");
+        assert_eq!(inverted["2"], "b");
+
+        let dupes: HashMap<String, String> = [("b", "x"), ("a", "x"), ("c", "y")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let multi = invert_multi(dupes.clone());
+        assert_eq!(multi["x"], vec!["a", "b"]);
+        assert_eq!(multi["y"], vec!["c"]);
+        assert_eq!(invert(dupes).len(), 2);
+    }
+
+    #[test]
+    fn test_safe_get_many() {
+        let mut map = HashMap::new();
+        map.insert("a".to_string(), "1".to_string());
+        map.insert("c".to_string(), "3".to_string());
+
+        let values = safe_get_many(&map, &["c", "b", "a"]);
+        assert_eq!(values, vec![Some(&"3".to_string()), None, Some(&"1".to_string())]);
+        assert!(std::ptr::eq(values[0].unwrap(), &map["c"]));
+    }
+
+    #[test]
+    fn test_chunk_exact() {
+        let items = [1, 2, 3, 4, 5, 6, 7];
+        let (arrays, rest) = chunk_exact::<3, _>(&items);
+        assert_eq!(arrays, vec![[1, 2, 3], [4, 5, 6]]);
+        assert_eq!(rest, &[7]);
+
+        let (arrays, rest) = chunk_exact::<4, _>(&items[..2]);
+        assert!(arrays.is_empty());
+        assert_eq!(rest, &[1, 2]);
+    }
 }
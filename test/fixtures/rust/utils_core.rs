@@ -0,0 +1,136 @@
+//! Pure data utilities that only need `alloc`.
+//!
+//! Nothing here touches `std`, so the module also builds in `no_std`
+//! contexts through the library target (see `lib.rs`).
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Flatten a nested vector.
+#[allow(dead_code)]
+pub fn flatten<T: Clone>(nested: Vec<Vec<T>>) -> Vec<T> {
+    nested.into_iter().flatten().collect()
+}
+
+/// Split a vector into chunks.
+#[allow(dead_code)]
+pub fn chunk<T: Clone>(items: Vec<T>, size: usize) -> Vec<Vec<T>> {
+    items.chunks(size).map(|c| c.to_vec()).collect()
+}
+
+/// Split a slice into fixed-size arrays plus the leftover tail.
+///
+/// Panics if `N` is zero.
+#[allow(dead_code)]
+pub fn chunk_exact<const N: usize, T: Copy>(items: &[T]) -> (Vec<[T; N]>, &[T]) {
+    let chunks = items.chunks_exact(N);
+    let remainder = chunks.remainder();
+    let arrays = chunks.map(|c| <[T; N]>::try_from(c).unwrap()).collect();
+    (arrays, remainder)
+}
+
+/// Split an identifier into words at separators, case changes, and digit runs.
+///
+/// Handles snake_case, kebab-case, camelCase, PascalCase and acronyms, so
+/// `parseHTTPResponse2Json` splits into `parse`, `HTTP`, `Response`, `2`, `Json`.
+/// Any non-alphanumeric character separates words and is dropped.
+#[allow(dead_code)]
+pub fn split_into_words(name: &str) -> Vec<String> {
+    let chars: Vec<char> = name.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+
+    for (i, &ch) in chars.iter().enumerate() {
+        if !ch.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(core::mem::take(&mut word));
+            }
+            continue;
+        }
+        if !word.is_empty() {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|c| c.is_lowercase());
+            let boundary = (prev.is_lowercase() && ch.is_uppercase())
+                || prev.is_numeric() != ch.is_numeric()
+                || (prev.is_uppercase() && ch.is_uppercase() && next_is_lower);
+            if boundary {
+                words.push(core::mem::take(&mut word));
+            }
+        }
+        word.push(ch);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+/// Lowercase a word, with full Unicode mapping or ASCII only.
+#[allow(dead_code)]
+fn lower(word: &str, unicode: bool) -> String {
+    if unicode {
+        word.to_lowercase()
+    } else {
+        word.to_ascii_lowercase()
+    }
+}
+
+/// Uppercase the first character of a word and lowercase the rest.
+#[allow(dead_code)]
+fn capitalize(word: &str, unicode: bool) -> String {
+    let mut chars = word.chars();
+    let mut result = String::new();
+    if let Some(first) = chars.next() {
+        if unicode {
+            result.extend(first.to_uppercase());
+        } else {
+            result.push(first.to_ascii_uppercase());
+        }
+    }
+    result.push_str(&lower(chars.as_str(), unicode));
+    result
+}
+
+/// Re-join the words of `name` as camelCase.
+#[allow(dead_code)]
+fn join_camel(name: &str, unicode: bool) -> String {
+    split_into_words(name)
+        .iter()
+        .enumerate()
+        .map(|(i, word)| if i == 0 { lower(word, unicode) } else { capitalize(word, unicode) })
+        .collect()
+}
+
+/// Re-join the words of `name` as snake_case.
+#[allow(dead_code)]
+fn join_snake(name: &str, unicode: bool) -> String {
+    let words: Vec<String> = split_into_words(name).iter().map(|w| lower(w, unicode)).collect();
+    words.join("_")
+}
+
+/// Convert snake_case to camelCase.
+#[allow(dead_code)]
+pub fn snake_to_camel(name: &str) -> String {
+    join_camel(name, false)
+}
+
+/// Convert camelCase to snake_case.
+#[allow(dead_code)]
+pub fn camel_to_snake(name: &str) -> String {
+    join_snake(name, false)
+}
+
+/// Convert snake_case to camelCase using full Unicode case mapping.
+///
+/// Uppercasing may expand one character into several, e.g. `ß` becomes `SS`.
+#[allow(dead_code)]
+pub fn snake_to_camel_unicode(name: &str) -> String {
+    join_camel(name, true)
+}
+
+/// Convert camelCase to snake_case using full Unicode case mapping.
+#[allow(dead_code)]
+pub fn camel_to_snake_unicode(name: &str) -> String {
+    join_snake(name, true)
+}
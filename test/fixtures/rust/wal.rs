@@ -0,0 +1,326 @@
+//! Append-only write-ahead log of task mutations.
+//!
+//! Each record is one line: the CRC-32 of the payload in hex, a tab, then the
+//! payload's tab-separated fields with `\`, tab and newline escaped. Logged
+//! tasks keep every field; timestamps are stored as nanoseconds since the Unix
+//! epoch, with earlier times clamped to the epoch. A batch is one line, so it
+//! is recovered whole or not at all. Records reach the OS before the mutation
+//! is applied but are not fsynced, so the log survives a process crash, not a
+//! power loss.
+
+use crate::checksum::crc32;
+use crate::{Claim, Status, Task};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// One logged mutation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WalRecord {
+    Add(Task),
+    /// Replace the stored task with the same ID, adding it if missing.
+    Put(Task),
+    Update {
+        id: String,
+        name: Option<String>,
+        status: Option<Status>,
+    },
+    Delete(String),
+    Clear,
+    /// Mutations applied together under one lock.
+    Batch(Vec<WalRecord>),
+}
+
+/// Open log file that records are appended to.
+#[derive(Debug)]
+pub struct Wal {
+    file: File,
+}
+
+impl Wal {
+    /// Open `path` for appending, creating it if needed.
+    pub fn open(path: &Path) -> io::Result<Wal> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Wal { file })
+    }
+
+    /// Append one record as a single write.
+    pub fn append(&mut self, record: &WalRecord) -> io::Result<()> {
+        let payload = encode(record);
+        let line = format!("{:08x}\t{}\n", crc32(payload.as_bytes()), payload);
+        self.file.write_all(line.as_bytes())
+    }
+}
+
+/// Read every intact record from `path`, truncating the file after the last one.
+///
+/// Reading stops at the first record that is incomplete, fails its checksum or
+/// does not parse; it and everything after it are cut off.
+pub fn recover(path: &Path) -> io::Result<Vec<WalRecord>> {
+    let data = std::fs::read(path)?;
+    let mut records = Vec::new();
+    let mut valid_len = 0;
+    for line in data.split_inclusive(|&b| b == b'\n') {
+        let Some(record) = line.strip_suffix(b"\n").and_then(decode_line) else {
+            break;
+        };
+        records.push(record);
+        valid_len += line.len();
+    }
+    if valid_len < data.len() {
+        log_at!(warn, "truncating {} corrupt WAL bytes", data.len() - valid_len);
+        OpenOptions::new().write(true).open(path)?.set_len(valid_len as u64)?;
+    }
+    Ok(records)
+}
+
+/// Check a line's checksum and parse its payload.
+fn decode_line(line: &[u8]) -> Option<WalRecord> {
+    let line = std::str::from_utf8(line).ok()?;
+    let (crc, payload) = line.split_once('\t')?;
+    if u32::from_str_radix(crc, 16).ok()? != crc32(payload.as_bytes()) {
+        return None;
+    }
+    decode(payload)
+}
+
+/// Serialize a record as escaped, tab-separated fields.
+fn encode(record: &WalRecord) -> String {
+    let mut fields: Vec<String> = Vec::new();
+    match record {
+        WalRecord::Add(task) => encode_task("add", task, &mut fields),
+        WalRecord::Put(task) => encode_task("put", task, &mut fields),
+        WalRecord::Update { id, name, status } => {
+            fields.extend(["update".to_string(), id.clone()]);
+            fields.push(name.as_ref().map_or(String::new(), |n| format!("+{}", n)));
+            fields.push(status.as_ref().map_or(String::new(), |s| format!("{:?}", s)));
+        }
+        WalRecord::Delete(id) => fields.extend(["delete".to_string(), id.clone()]),
+        WalRecord::Clear => fields.push("clear".to_string()),
+        WalRecord::Batch(records) => {
+            fields.push("batch".to_string());
+            fields.extend(records.iter().map(encode));
+        }
+    }
+    fields.iter().map(|f| escape(f)).collect::<Vec<_>>().join("\t")
+}
+
+/// Append a task's fields after `tag`: scalars first, then the dependency
+/// count and IDs, then metadata pairs sorted by key.
+fn encode_task(tag: &str, task: &Task, fields: &mut Vec<String>) {
+    fields.extend([tag.to_string(), task.id.clone(), task.name.clone()]);
+    fields.push(format!("{:?}", task.status));
+    fields.push(task.priority.to_string());
+    fields.push(encode_time(task.created_at));
+    fields.push(task.version.to_string());
+    fields.push(task.expires_at.map_or(String::new(), encode_time));
+    fields.push(task.deadline.map_or(String::new(), encode_time));
+    match &task.claim {
+        Some(claim) => {
+            fields.push(format!("+{}", claim.worker_id));
+            fields.push(encode_time(claim.expires_at));
+        }
+        None => fields.extend([String::new(), String::new()]),
+    }
+    fields.push(task.depends_on.len().to_string());
+    fields.extend(task.depends_on.iter().cloned());
+    let mut metadata: Vec<(&String, &String)> = task.metadata.iter().collect();
+    metadata.sort();
+    for (key, value) in metadata {
+        fields.push(key.to_string());
+        fields.push(value.clone());
+    }
+}
+
+/// Parse the fields written by `encode_task`, tag excluded.
+fn decode_task(fields: &[String]) -> Option<Task> {
+    let [
+        id,
+        name,
+        status,
+        priority,
+        created_at,
+        version,
+        expires_at,
+        deadline,
+        worker,
+        lease,
+        deps,
+        rest @ ..,
+    ] = fields
+    else {
+        return None;
+    };
+    let deps: usize = deps.parse().ok()?;
+    if rest.len() < deps || !(rest.len() - deps).is_multiple_of(2) {
+        return None;
+    }
+    let (depends_on, metadata) = rest.split_at(deps);
+    let mut task = Task::new(id.clone(), name.clone())
+        .with_status(status.parse().ok()?)
+        .with_priority(priority.parse().ok()?);
+    task.created_at = decode_time(created_at)?;
+    task.version = version.parse().ok()?;
+    task.expires_at = decode_optional_time(expires_at)?;
+    task.deadline = decode_optional_time(deadline)?;
+    task.claim = match worker.as_str() {
+        "" => None,
+        w => Some(Claim {
+            worker_id: w.strip_prefix('+')?.to_string(),
+            expires_at: decode_time(lease)?,
+        }),
+    };
+    task.depends_on = depends_on.to_vec();
+    for pair in metadata.chunks(2) {
+        task.metadata.insert(pair[0].clone(), pair[1].clone());
+    }
+    Some(task)
+}
+
+/// Encode a time as nanoseconds since the Unix epoch.
+fn encode_time(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    u64::try_from(since.as_nanos()).unwrap_or(u64::MAX).to_string()
+}
+
+/// Undo `encode_time`.
+fn decode_time(field: &str) -> Option<SystemTime> {
+    Some(UNIX_EPOCH + Duration::from_nanos(field.parse().ok()?))
+}
+
+/// Decode an optional time, where an empty field means `None`.
+fn decode_optional_time(field: &str) -> Option<Option<SystemTime>> {
+    match field {
+        "" => Some(None),
+        t => decode_time(t).map(Some),
+    }
+}
+
+/// Parse a payload written by `encode`.
+fn decode(payload: &str) -> Option<WalRecord> {
+    let fields: Vec<String> = payload.split('\t').map(unescape).collect::<Option<_>>()?;
+    match fields.first()?.as_str() {
+        "add" => decode_task(&fields[1..]).map(WalRecord::Add),
+        "put" => decode_task(&fields[1..]).map(WalRecord::Put),
+        "update" if fields.len() == 4 => Some(WalRecord::Update {
+            id: fields[1].clone(),
+            name: match fields[2].as_str() {
+                "" => None,
+                n => Some(n.strip_prefix('+')?.to_string()),
+            },
+            status: match fields[3].as_str() {
+                "" => None,
+                s => Some(s.parse().ok()?),
+            },
+        }),
+        "delete" if fields.len() == 2 => Some(WalRecord::Delete(fields[1].clone())),
+        "clear" if fields.len() == 1 => Some(WalRecord::Clear),
+        "batch" => fields[1..]
+            .iter()
+            .map(|payload| decode(payload))
+            .collect::<Option<_>>()
+            .map(WalRecord::Batch),
+        _ => None,
+    }
+}
+
+/// Escape `\`, tab and newline so a field fits on one tab-separated line.
+fn escape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Undo `escape`; `None` on a dangling or unknown escape.
+fn unescape(field: &str) -> Option<String> {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '\\' => out.push('\\'),
+            't' => out.push('\t'),
+            'n' => out.push('\n'),
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wal_record_round_trip() {
+        let task = Task::new("1".to_string(), "Tab\there\nand \\ slash".to_string())
+            .with_status(Status::Active)
+            .with_metadata("k", "v\tw");
+        let records = [
+            WalRecord::Add(task.clone()),
+            WalRecord::Put(task.clone()),
+            WalRecord::Batch(vec![
+                WalRecord::Delete("0".to_string()),
+                WalRecord::Put(task.clone()),
+                WalRecord::Batch(vec![WalRecord::Clear]),
+            ]),
+            WalRecord::Batch(Vec::new()),
+            WalRecord::Update {
+                id: "1".to_string(),
+                name: Some(String::new()),
+                status: None,
+            },
+            WalRecord::Delete("1".to_string()),
+            WalRecord::Clear,
+        ];
+        for record in records {
+            let payload = encode(&record);
+            assert!(!payload.contains('\n'));
+            assert_eq!(decode(&payload), Some(record));
+        }
+        assert_eq!(decode("delete\tbad\\x"), None);
+    }
+
+    #[test]
+    fn test_wal_task_keeps_every_field() {
+        let mut task = Task::new("1".to_string(), "Full".to_string())
+            .with_dependency("0")
+            .with_dependency("2")
+            .with_deadline(UNIX_EPOCH + Duration::from_secs(90))
+            .with_metadata("k", "v");
+        task.version = 4;
+        task.expires_at = Some(UNIX_EPOCH + Duration::from_nanos(1_500));
+        task.claim = Some(Claim {
+            worker_id: "w\t1".to_string(),
+            expires_at: UNIX_EPOCH + Duration::from_secs(60),
+        });
+        let Some(WalRecord::Put(decoded)) = decode(&encode(&WalRecord::Put(task.clone()))) else {
+            panic!("put did not round-trip");
+        };
+        assert_eq!(decoded.created_at, task.created_at);
+        assert_eq!(decoded.version, 4);
+        assert_eq!(decoded.expires_at, task.expires_at);
+        assert_eq!(decoded.deadline, task.deadline);
+        assert_eq!(decoded.claim, task.claim);
+        assert_eq!(decoded.depends_on, vec!["0", "2"]);
+        assert_eq!(decoded.metadata["k"], "v");
+
+        task.claim = None;
+        task.deadline = None;
+        let Some(WalRecord::Add(decoded)) = decode(&encode(&WalRecord::Add(task))) else {
+            panic!("add did not round-trip");
+        };
+        assert_eq!((decoded.claim, decoded.deadline), (None, None));
+    }
+}
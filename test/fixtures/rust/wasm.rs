@@ -0,0 +1,99 @@
+//! `wasm-bindgen` wrappers exposing task management to JavaScript.
+//!
+//! Errors surface as JS exceptions and tasks are returned as plain objects
+//! with the same string fields as `format_task`.
+
+use crate::{format_task, Task, TaskManager};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+/// Task handle constructed from JS.
+#[wasm_bindgen(js_name = Task)]
+pub struct WasmTask {
+    inner: Task,
+}
+
+#[wasm_bindgen(js_class = Task)]
+impl WasmTask {
+    /// Create a pending task.
+    #[wasm_bindgen(constructor)]
+    pub fn new(id: String, name: String) -> WasmTask {
+        WasmTask { inner: Task::new(id, name) }
+    }
+
+    /// Set the task priority.
+    #[wasm_bindgen(js_name = withPriority)]
+    pub fn with_priority(self, priority: i32) -> WasmTask {
+        WasmTask { inner: self.inner.with_priority(priority) }
+    }
+}
+
+/// Task manager exposed to JS.
+#[wasm_bindgen(js_name = TaskManager)]
+pub struct WasmTaskManager {
+    inner: TaskManager,
+}
+
+#[wasm_bindgen(js_class = TaskManager)]
+impl WasmTaskManager {
+    /// Create an empty manager.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmTaskManager {
+        WasmTaskManager { inner: TaskManager::new() }
+    }
+
+    /// Add a task, throwing if it fails validation.
+    #[wasm_bindgen(js_name = addTask)]
+    pub fn add_task(&self, task: WasmTask) -> Result<(), JsError> {
+        self.inner.add_task(task.inner).map_err(|e| JsError::new(&e))
+    }
+
+    /// Get a task as a plain object, or `undefined` if it is missing.
+    #[wasm_bindgen(js_name = getTask)]
+    pub fn get_task(&self, id: &str) -> Result<JsValue, JsError> {
+        match self.inner.get_task(id) {
+            Some(task) => to_js(&task),
+            None => Ok(JsValue::UNDEFINED),
+        }
+    }
+
+    /// Get the number of tasks.
+    pub fn count(&self) -> usize {
+        self.inner.count()
+    }
+}
+
+impl Default for WasmTaskManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Convert a task to a JS object rather than a `Map`.
+fn to_js(task: &Task) -> Result<JsValue, JsError> {
+    let serializer = serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
+    Ok(format_task(task).serialize(&serializer)?)
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test]
+    fn test_add_get_round_trip() {
+        let manager = WasmTaskManager::new();
+        let task = WasmTask::new("1".to_string(), "Docs".to_string()).with_priority(3);
+        assert!(manager.add_task(task).is_ok());
+        assert!(manager.add_task(WasmTask::new("2".to_string(), String::new())).is_err());
+        assert_eq!(manager.count(), 1);
+
+        let value = manager.get_task("1").ok().unwrap();
+        assert!(value.is_object());
+        let fields: HashMap<String, String> = serde_wasm_bindgen::from_value(value).unwrap();
+        assert_eq!(fields["name"], "Docs");
+        assert_eq!(fields["priority"], "3");
+        assert!(manager.get_task("missing").ok().unwrap().is_undefined());
+    }
+}